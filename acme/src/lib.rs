@@ -1,12 +1,23 @@
 use anyhow::Context;
 
+mod local_key;
+pub use local_key::LocalKey;
+
+mod tls_alpn_01;
+pub use tls_alpn_01::TlsAlpn01Responder;
+
 pub struct Client<'a> {
 	inner: http_common::Client,
 
+	key_change_url: http_common::Uri,
 	new_account_url: http_common::Uri,
+	new_authz_url: Option<http_common::Uri>,
 	new_nonce_url: http_common::Uri,
 	new_order_url: http_common::Uri,
 	renewal_info_url: Option<http_common::Uri>,
+	revoke_cert_url: http_common::Uri,
+
+	meta: DirectoryMeta,
 
 	logger: &'a log2::Logger,
 }
@@ -14,15 +25,25 @@ pub struct Client<'a> {
 pub struct Account<'a, K> {
 	inner: http_common::Client,
 
+	key_change_url: http_common::Uri,
+	new_authz_url: Option<http_common::Uri>,
 	new_nonce_url: http_common::Uri,
 	new_order_url: http_common::Uri,
+	revoke_cert_url: http_common::Uri,
+
+	profiles: std::collections::BTreeMap<String, String>,
 
 	logger: &'a log2::Logger,
 
 	account_key: &'a K,
 	account_url: Option<String>,
+	contact_urls: Vec<String>,
 
-	nonce: Option<http_common::HeaderValue>,
+	polling_phase_timeout: Option<std::time::Duration>,
+	polling_deadline: Option<tokio::time::Instant>,
+
+	// Taken by each request and replaced by the nonce in its response, so concurrent requests never use the same nonce.
+	nonce: std::sync::Mutex<Option<http_common::HeaderValue>>,
 }
 
 impl<'a> Client<'a> {
@@ -33,9 +54,15 @@ impl<'a> Client<'a> {
 	) -> anyhow::Result<Self> {
 		#[derive(Debug, serde::Deserialize)]
 		struct DirectoryResponse {
+			#[serde(rename = "keyChange")]
+			key_change_url: http_common::DeserializableUri,
+
 			#[serde(rename = "newAccount")]
 			new_account_url: http_common::DeserializableUri,
 
+			#[serde(rename = "newAuthz")]
+			new_authz_url: Option<http_common::DeserializableUri>,
+
 			#[serde(rename = "newNonce")]
 			new_nonce_url: http_common::DeserializableUri,
 
@@ -44,6 +71,12 @@ impl<'a> Client<'a> {
 
 			#[serde(rename = "renewalInfo")]
 			renewal_info_url: Option<http_common::DeserializableUri>,
+
+			#[serde(rename = "revokeCert")]
+			revoke_cert_url: http_common::DeserializableUri,
+
+			#[serde(default)]
+			meta: DirectoryMeta,
 		}
 
 		impl http_common::FromResponse for DirectoryResponse {
@@ -62,10 +95,14 @@ impl<'a> Client<'a> {
 		let inner = http_common::Client::new(user_agent).context("could not create HTTP client")?;
 
 		let DirectoryResponse {
+			key_change_url: http_common::DeserializableUri(key_change_url),
 			new_account_url: http_common::DeserializableUri(new_account_url),
+			new_authz_url,
 			new_nonce_url: http_common::DeserializableUri(new_nonce_url),
 			new_order_url: http_common::DeserializableUri(new_order_url),
 			renewal_info_url,
+			revoke_cert_url: http_common::DeserializableUri(revoke_cert_url),
+			meta,
 		} = logger.report_operation("acme/directory", &acme_directory_url.clone(), <log2::ScopedObjectOperation>::Get, async {
 			let mut req = http_common::Request::new(Default::default());
 			*req.method_mut() = http_common::Method::GET;
@@ -77,35 +114,90 @@ impl<'a> Client<'a> {
 
 		Ok(Client {
 			inner,
+			key_change_url,
 			new_account_url,
+			new_authz_url: new_authz_url.map(|http_common::DeserializableUri(new_authz_url)| new_authz_url),
 			new_nonce_url,
 			new_order_url,
 			renewal_info_url: renewal_info_url.map(|http_common::DeserializableUri(renewal_info_url)| renewal_info_url),
+			revoke_cert_url,
+			meta,
 			logger,
 		})
 	}
 
-	pub async fn renewal_suggested_window_start(&mut self, ari_id: &str) -> anyhow::Result<Option<time::OffsetDateTime>> {
+	/// The metadata in the ACME server's directory.
+	pub fn meta(&self) -> &DirectoryMeta {
+		&self.meta
+	}
+
+	/// The names and descriptions of the certificate profiles offered by the ACME server.
+	pub fn profiles(&self) -> &std::collections::BTreeMap<String, String> {
+		&self.meta.profiles
+	}
+
+	/// Returns an error if the ACME server does not offer the given certificate profile.
+	pub fn check_profile(&self, profile: &str) -> anyhow::Result<()> {
+		check_profile(&self.meta.profiles, profile)
+	}
+
+	/// Returns the renewal information of the certificate with the given ARI ID, or `None` if the ACME server does not support ARI.
+	///
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc9773>
+	pub async fn renewal_info(&mut self, ari_id: &str) -> anyhow::Result<Option<RenewalInfo>> {
 		#[derive(Debug, serde::Deserialize)]
-		struct Response {
+		struct ResponseInner {
 			#[serde(rename = "suggestedWindow")]
 			suggested_window: SuggestedWindow,
+
+			#[serde(rename = "explanationURL")]
+			explanation_url: Option<String>,
 		}
 
 		#[derive(Debug, serde::Deserialize)]
 		struct SuggestedWindow {
 			#[serde(deserialize_with = "time::serde::rfc3339::deserialize")]
 			start: time::OffsetDateTime,
+
+			#[serde(deserialize_with = "time::serde::rfc3339::deserialize")]
+			end: time::OffsetDateTime,
 		}
 
+		struct Response(RenewalInfo);
+
 		impl http_common::FromResponse for Response {
 			fn from_response(
 				status: http_common::StatusCode,
 				body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
-				_headers: http_common::HeaderMap,
+				headers: http_common::HeaderMap,
 			) -> anyhow::Result<Option<Self>> {
 				Ok(match (status, body) {
-					(http_common::StatusCode::OK, Some(body)) => Some(body.as_json()?),
+					(http_common::StatusCode::OK, Some(body)) => {
+						let ResponseInner {
+							suggested_window: SuggestedWindow { start, end },
+							explanation_url,
+						} = body.as_json()?;
+
+						if end < start {
+							return Err(anyhow::anyhow!("suggested window ends before it starts"));
+						}
+
+						let retry_after =
+							if headers.contains_key(http_common::RETRY_AFTER) {
+								Some(http_common::get_retry_after(&headers, std::time::Duration::ZERO, std::time::Duration::MAX)?)
+							}
+							else {
+								None
+							};
+
+						Some(Response(RenewalInfo {
+							suggested_window_start: start,
+							suggested_window_end: end,
+							explanation_url,
+							retry_after,
+						}))
+					},
+
 					_ => None,
 				})
 			}
@@ -120,24 +212,69 @@ impl<'a> Client<'a> {
 		renewal_info_url.push_str(ari_id);
 		let renewal_info_url: http_common::Uri = renewal_info_url.try_into().context("could not construct renewal info URL")?;
 
-		let response = self.logger.report_operation("acme/renewalInfo", &renewal_info_url.clone(), <log2::ScopedObjectOperation>::Get, async {
+		let renewal_info = self.logger.report_operation("acme/renewalInfo", &renewal_info_url.clone(), <log2::ScopedObjectOperation>::Get, async {
 			let mut req = http_common::Request::new(Default::default());
 			*req.method_mut() = http_common::Method::GET;
 			*req.uri_mut() = renewal_info_url;
 
-			// Suppress ARI errors so that the caller falls back to not-after-based calculation.
-			let body = self.inner.request(req).await.ok();
-			Ok::<_, anyhow::Error>(body)
+			let Response(renewal_info) = self.inner.request(req).await.context("could not execute HTTP request")?;
+			Ok::<_, anyhow::Error>(renewal_info)
 		}).await.context("could not query ACME renewal info")?;
 
-		let start = response.map(|Response { suggested_window: SuggestedWindow { start } }| start);
-		Ok(start)
+		Ok(Some(renewal_info))
 	}
 
+	/// Registers a new account with the given account key, or returns the existing account if there is one.
+	///
+	/// The contact URL of an existing account is not updated. Use [`Account::update_contacts`] for that.
+	///
+	/// `external_account_binding` is required if the ACME server requires External Account Binding.
 	pub async fn new_account<K>(
 		self,
 		acme_contact_url: &str,
 		account_key: &'a K,
+		external_account_binding: Option<ExternalAccountBinding<'_>>,
+	) -> anyhow::Result<Account<'a, K>>
+	where
+		K: AccountKey,
+	{
+		if self.meta.external_account_required && external_account_binding.is_none() {
+			return Err(anyhow::anyhow!("ACME server requires external account binding but it was not provided"));
+		}
+
+		let external_account_binding =
+			external_account_binding
+			.map(|external_account_binding| external_account_binding.sign(&account_key.as_jwk(), &self.new_account_url))
+			.transpose()?;
+
+		self.account(account_key, &NewAccountRequest {
+			contact_urls: Some(&[acme_contact_url]),
+			external_account_binding,
+			only_return_existing: false,
+			terms_of_service_agreed: Some(true),
+		}).await
+	}
+
+	/// Returns the existing account of the given account key. Fails if there is no such account.
+	pub async fn find_account<K>(
+		self,
+		account_key: &'a K,
+	) -> anyhow::Result<Account<'a, K>>
+	where
+		K: AccountKey,
+	{
+		self.account(account_key, &NewAccountRequest {
+			contact_urls: None,
+			external_account_binding: None,
+			only_return_existing: true,
+			terms_of_service_agreed: None,
+		}).await
+	}
+
+	async fn account<K>(
+		self,
+		account_key: &'a K,
+		request: &NewAccountRequest<'_>,
 	) -> anyhow::Result<Account<'a, K>>
 	where
 		K: AccountKey,
@@ -145,10 +282,15 @@ impl<'a> Client<'a> {
 		let Client {
 			inner,
 
+			key_change_url,
 			new_account_url,
+			new_authz_url,
 			new_nonce_url,
 			new_order_url,
 			renewal_info_url: _,
+			revoke_cert_url,
+
+			meta: DirectoryMeta { profiles, .. },
 
 			logger,
 		} = self;
@@ -156,176 +298,216 @@ impl<'a> Client<'a> {
 		let mut account = Account {
 			inner,
 
+			key_change_url,
+			new_authz_url,
 			new_nonce_url,
 			new_order_url,
+			revoke_cert_url,
+
+			profiles,
 
 			logger,
 
 			account_key,
 			account_url: None,
+			contact_urls: vec![],
 
-			nonce: None,
+			polling_phase_timeout: None,
+			polling_deadline: None,
+
+			nonce: Default::default(),
 		};
 
-		account.account_url = {
-			#[derive(serde::Serialize)]
-			struct NewAccountRequest<'a> {
-				#[serde(rename = "contact")]
-				contact_urls: &'a [&'a str],
+		let (account_url, AccountResponse { status, contact_urls }) = logger.report_operation("acme/account", "", <log2::ScopedObjectOperation>::Get, async {
+			let http_common::ResponseWithLocation {
+				body: response,
+				location: account_url,
+			} =
+				account.post(new_account_url, Some(request)).await
+				.context(if request.only_return_existing { "could not get account" } else { "could not create / get account" })?;
+			Ok::<_, anyhow::Error>((account_url.to_string(), response))
+		}).await?;
 
-				#[serde(rename = "termsOfServiceAgreed")]
-				terms_of_service_agreed: bool,
-			}
+		logger.report_state("acme/account", &account_url, format_args!("{status:?}"));
 
-			#[derive(serde::Deserialize)]
-			struct NewAccountResponse {
-				status: AccountStatus,
-			}
+		if !matches!(status, AccountStatus::Valid) {
+			return Err(anyhow::anyhow!("Account has {status:?} status"));
+		}
 
-			#[derive(Debug, serde::Deserialize)]
-			enum AccountStatus {
-				#[serde(rename = "deactivated")]
-				Deactivated,
-				#[serde(rename = "revoked")]
-				Revoked,
-				#[serde(rename = "valid")]
-				Valid,
-			}
+		account.account_url = Some(account_url);
+		account.contact_urls = contact_urls;
 
-			impl http_common::FromResponse for NewAccountResponse {
-				fn from_response(
-					status: http_common::StatusCode,
-					body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
-					_headers: http_common::HeaderMap,
-				) -> anyhow::Result<Option<Self>> {
-					Ok(match (status, body) {
-						(http_common::StatusCode::CREATED | http_common::StatusCode::OK, Some(body)) => Some(body.as_json()?),
-						_ => None,
-					})
-				}
-			}
+		Ok(account)
+	}
+}
 
-			let (account_url, status) = logger.report_operation("acme/account", "", <log2::ScopedObjectOperation>::Get, async {
-				let http_common::ResponseWithLocation {
-					body: NewAccountResponse { status },
-					location: account_url,
-				} =
-					account.post(new_account_url, Some(&NewAccountRequest {
-						contact_urls: &[acme_contact_url],
-						terms_of_service_agreed: true,
-					})).await.context("could not create / get account")?;
-				Ok::<_, anyhow::Error>((account_url.to_string(), status))
-			}).await?;
+impl<'a, K> Account<'a, K> where K: AccountKey {
+	pub fn account_url(&self) -> Option<&str> {
+		self.account_url.as_deref()
+	}
 
-			logger.report_state("acme/account", &account_url, format_args!("{status:?}"));
+	pub fn contact_urls(&self) -> &[String] {
+		&self.contact_urls
+	}
 
-			if !matches!(status, AccountStatus::Valid) {
-				return Err(anyhow::anyhow!("Account has {status:?} status"));
-			}
+	/// Limits how long `place_order`, `complete_authorization` and `finalize_order` poll the ACME server.
+	///
+	/// `phase_timeout` applies to each of those calls individually. `overall_timeout` starts now and applies to all of them together.
+	pub fn set_polling_timeouts(&mut self, phase_timeout: Option<std::time::Duration>, overall_timeout: Option<std::time::Duration>) {
+		self.polling_phase_timeout = phase_timeout;
+		self.polling_deadline = overall_timeout.map(|overall_timeout| tokio::time::Instant::now() + overall_timeout);
+	}
 
-			Some(account_url)
-		};
+	pub async fn update_contacts(&mut self, contact_urls: &[&str]) -> anyhow::Result<()> {
+		#[derive(serde::Serialize)]
+		struct UpdateAccountRequest<'a> {
+			#[serde(rename = "contact")]
+			contact_urls: &'a [&'a str],
+		}
 
-		Ok(account)
+		let account_url = self.account_url.clone().context("account URL is not known")?;
+
+		let AccountResponse { status, contact_urls } =
+			self.logger.report_operation(
+				"acme/account",
+				&account_url.clone(),
+				log2::ScopedObjectOperation::Create { value: format_args!("{contact_urls:?}") },
+				async {
+					let response =
+						self.post(account_url.try_into().context("could not parse account URL")?, Some(&UpdateAccountRequest {
+							contact_urls,
+						})).await.context("could not update account")?;
+					Ok::<_, anyhow::Error>(response)
+				},
+			).await?;
+
+		if !matches!(status, AccountStatus::Valid) {
+			return Err(anyhow::anyhow!("Account has {status:?} status"));
+		}
+
+		self.contact_urls = contact_urls;
+
+		Ok(())
 	}
-}
 
-impl<K> Account<'_, K> where K: AccountKey {
-	pub async fn place_order(&mut self, domain_name: &str) -> anyhow::Result<Order> {
+	/// Deactivates the account. The account can no longer be used after this.
+	///
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-7.3.6>
+	pub async fn deactivate(self) -> anyhow::Result<()> {
 		#[derive(serde::Serialize)]
-		struct NewOrderRequest<'a> {
-			identifiers: &'a [NewOrderRequestIdentifier<'a>],
+		struct DeactivateAccountRequest {
+			status: AccountStatus,
+		}
+
+		let account_url = self.account_url.clone().context("account URL is not known")?;
+
+		let AccountResponse { status, contact_urls: _ } = self.logger.report_operation("acme/account", &account_url.clone(), <log2::ScopedObjectOperation>::Delete, async {
+			let response =
+				self.post(account_url.try_into().context("could not parse account URL")?, Some(&DeactivateAccountRequest {
+					status: AccountStatus::Deactivated,
+				})).await.context("could not deactivate account")?;
+			Ok::<_, anyhow::Error>(response)
+		}).await?;
+
+		if !matches!(status, AccountStatus::Deactivated) {
+			return Err(anyhow::anyhow!("Account has {status:?} status"));
 		}
 
+		Ok(())
+	}
+
+	/// `challenge_types` is in order of preference. Each authorization uses the first of them that the ACME server offers for it.
+	///
+	/// `replaces` is the ARI ID of the certificate that this order is a renewal of, if any.
+	///
+	/// `profile` is the name of one of the profiles offered by the ACME server, if any.
+	///
+	/// `not_before` and `not_after` are the requested validity bounds of the certificate, if any. Not all ACME servers honor them.
+	pub async fn place_order(
+		&mut self,
+		identifiers: &[Identifier],
+		challenge_types: &[ChallengeType],
+		replaces: Option<&str>,
+		profile: Option<&str>,
+		not_before: Option<time::OffsetDateTime>,
+		not_after: Option<time::OffsetDateTime>,
+	) -> anyhow::Result<Order> {
 		#[derive(serde::Serialize)]
-		struct NewOrderRequestIdentifier<'a> {
-			r#type: &'a str,
-			value: &'a str,
+		struct NewOrderRequest<'a> {
+			identifiers: &'a [Identifier],
+
+			#[serde(rename = "notAfter", skip_serializing_if = "Option::is_none", serialize_with = "time::serde::rfc3339::option::serialize")]
+			not_after: Option<time::OffsetDateTime>,
+
+			#[serde(rename = "notBefore", skip_serializing_if = "Option::is_none", serialize_with = "time::serde::rfc3339::option::serialize")]
+			not_before: Option<time::OffsetDateTime>,
+
+			#[serde(skip_serializing_if = "Option::is_none")]
+			profile: Option<&'a str>,
+
+			#[serde(skip_serializing_if = "Option::is_none")]
+			replaces: Option<&'a str>,
 		}
 
-		#[derive(Debug, serde::Deserialize)]
-		struct OrderObjPending {
-			#[serde(rename = "authorizations")]
-			authorization_urls: Vec<http_common::DeserializableUri>,
+		if identifiers.is_empty() {
+			return Err(anyhow::anyhow!("order must have at least one identifier"));
+		}
+
+		if let Some(profile) = profile {
+			check_profile(&self.profiles, profile)?;
 		}
 
-		let (order_url, mut order) = self.logger.report_operation("acme/order", domain_name, <log2::ScopedObjectOperation>::Get, async {
+		let order_id = identifiers.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+
+		let (order_url, order) = self.logger.report_operation("acme/order", &order_id, <log2::ScopedObjectOperation>::Get, async {
 			let http_common::ResponseWithLocation {
 				location: order_url,
 				body: order,
-			} =
+			}: http_common::ResponseWithLocation<OrderResponse<OrderObjPending, serde::de::IgnoredAny>> =
 				self.post(self.new_order_url.clone(), Some(&NewOrderRequest {
-					identifiers: &[
-						NewOrderRequestIdentifier {
-							r#type: "dns",
-							value: domain_name,
-						},
-						NewOrderRequestIdentifier {
-							r#type: "dns",
-							value: &format!("*.{domain_name}"),
-						},
-					],
+					identifiers,
+					not_after,
+					not_before,
+					profile,
+					replaces,
 				})).await.context("could not create / get order")?;
 			Ok::<_, anyhow::Error>((order_url, order))
 		}).await?;
 
-		let order = loop {
-			self.logger.report_state("acme/order", &order_url, format_args!("{order:?}"));
+		if not_before.is_some() || not_after.is_some() {
+			self.logger.report_message(format_args!(
+				"Requested certificate validity {not_before:?} - {not_after:?}, ACME server set {:?} - {:?}",
+				order.not_before, order.not_after,
+			));
+		}
 
-			match order {
-				OrderResponse::Pending(OrderObjPending { authorization_urls }) => {
-					#[derive(Debug)]
-					enum AuthorizationResponse {
-						Pending { hasher: sha2::Sha256, challenge_url: http_common::Uri },
-						Valid,
-					}
+		self.poll_order(order_url, order, challenge_types).await
+	}
 
-					impl http_common::FromResponse for AuthorizationResponse {
-						fn from_response(
-							status: http_common::StatusCode,
-							body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
-							_headers: http_common::HeaderMap,
-						) -> anyhow::Result<Option<Self>> {
-							#[derive(serde::Deserialize)]
-							struct AuthorizationPending<'a> {
-								#[serde(borrow)]
-								challenges: Vec<Challenge<ChallengePending<'a>>>,
-							}
+	/// Gets an existing order, such as one created by an earlier call to [`Account::place_order`] that was not driven to completion.
+	pub async fn get_order(&mut self, order_url: http_common::Uri, challenge_types: &[ChallengeType]) -> anyhow::Result<Order> {
+		let order = self.logger.report_operation("acme/order", &order_url, <log2::ScopedObjectOperation>::Get, async {
+			let order: OrderResponse<OrderObjPending, serde::de::IgnoredAny> =
+				self.post(order_url.clone(), None::<&()>).await.context("could not get order")?;
+			Ok::<_, anyhow::Error>(order)
+		}).await?;
 
-							#[derive(serde::Deserialize)]
-							struct ChallengePending<'a> {
-								#[serde(borrow)]
-								token: std::borrow::Cow<'a, str>,
-								#[serde(borrow)]
-								r#type: std::borrow::Cow<'a, str>,
-								url: http_common::DeserializableUri,
-							}
+		self.poll_order(order_url, order, challenge_types).await
+	}
 
-							Ok(match (status, body) {
-								(http_common::StatusCode::OK, Some(body)) => Some(match body.as_json()? {
-									Authorization::Pending(AuthorizationPending { challenges }) => {
-										let (token, challenge_url) =
-											challenges.into_iter()
-											.find_map(|challenge| match challenge {
-												Challenge::Pending(ChallengePending { token, r#type, url: http_common::DeserializableUri(url) }) =>
-													(r#type == "dns-01").then_some((token, url)),
-												Challenge::Processing |
-												Challenge::Valid => None,
-											})
-											.context("did not find any pending dns-01 challenges")?;
-										let mut hasher: sha2::Sha256 = sha2::Digest::new();
-										sha2::Digest::update(&mut hasher, token.as_bytes());
-										AuthorizationResponse::Pending { hasher, challenge_url }
-									},
-
-									Authorization::Valid => AuthorizationResponse::Valid,
-								}),
+	async fn poll_order(
+		&mut self,
+		order_url: http_common::Uri,
+		mut order: OrderResponse<OrderObjPending, serde::de::IgnoredAny>,
+		challenge_types: &[ChallengeType],
+	) -> anyhow::Result<Order> {
+		let deadline = self.phase_deadline();
 
-								_ => None,
-							})
-						}
-					}
+		let order = loop {
+			self.logger.report_state("acme/order", &order_url, format_args!("{order:?}"));
 
+			match order.status {
+				OrderStatus::Pending(OrderObjPending { authorization_urls }) => {
 					let mut authorizations = Vec::with_capacity(authorization_urls.len());
 
 					for http_common::DeserializableUri(authorization_url) in authorization_urls {
@@ -333,34 +515,13 @@ impl<K> Account<'_, K> where K: AccountKey {
 
 						self.logger.report_state("acme/authorization", &authorization_url, format_args!("{authorization:?}"));
 
-						let (mut hasher, challenge_url) = match authorization {
-							AuthorizationResponse::Pending { hasher, challenge_url } => (hasher, challenge_url),
-							AuthorizationResponse::Valid => continue,
-						};
-
-						sha2::Digest::update(&mut hasher, b".");
-
-						let jwk_thumbprint = {
-							let mut hasher: sha2::Sha256 = sha2::Digest::new();
-							let mut serializer = serde_json::Serializer::new(&mut hasher);
-							serde::Serialize::serialize(&self.account_key.as_jwk(), &mut serializer).expect("cannot fail to serialize JWK");
-							sha2::Digest::finalize(hasher)
-						};
-
-						let hasher = {
-							let mut writer = base64::write::EncoderWriter::new(hasher, &JWS_BASE64_ENGINE);
-							std::io::Write::write_all(&mut writer, &jwk_thumbprint).expect("cannot fail to base64-encode JWK hash");
-							writer.finish().expect("cannot fail to base64-encode JWK hash")
+						let (identifier, wildcard, challenges) = match authorization {
+							AuthorizationWithChallengesResponse::Pending { identifier, wildcard, challenges } => (identifier, wildcard, challenges),
+							AuthorizationWithChallengesResponse::Valid => continue,
+							AuthorizationWithChallengesResponse::Failed(err) => return Err(err.into()),
 						};
 
-						let hash = sha2::Digest::finalize(hasher);
-						let dns_txt_record_content = base64::Engine::encode(&JWS_BASE64_ENGINE, hash);
-
-						authorizations.push(OrderPendingAuthorization {
-							authorization_url,
-							challenge_url,
-							dns_txt_record_content,
-						});
+						authorizations.push(self.pending_authorization(authorization_url, identifier, wildcard, challenges, challenge_types)?);
 					}
 
 					break Order::Pending(OrderPending {
@@ -369,18 +530,21 @@ impl<K> Account<'_, K> where K: AccountKey {
 					});
 				},
 
-				OrderResponse::Processing { retry_after } => {
+				OrderStatus::Processing { retry_after } => {
 					self.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking order..."));
-					tokio::time::sleep(retry_after).await;
+					self.sleep_before_polling(deadline, retry_after, "acme/order", &order_url, &order).await?;
 				},
 
-				OrderResponse::Ready(serde::de::IgnoredAny) => break Order::Ready(OrderReady {
+				OrderStatus::Ready(serde::de::IgnoredAny) => break Order::Ready(OrderReady {
 					order_url,
 				}),
 
-				OrderResponse::Valid { certificate_url } => break Order::Valid(OrderValid {
+				OrderStatus::Valid { certificate_url } => break Order::Valid(OrderValid {
+					order_url,
 					certificate_url,
 				}),
+
+				OrderStatus::Invalid { error } => return Err(StatusError::order(error).into()),
 			}
 
 			order = self.post(order_url.clone(), None::<&()>).await.context("could not get order")?;
@@ -389,13 +553,143 @@ impl<K> Account<'_, K> where K: AccountKey {
 		Ok(order)
 	}
 
+	/// Creates an authorization for `identifier` ahead of placing an order for it.
+	///
+	/// Returns `None` if the identifier is already authorized. Otherwise the returned authorization must be completed
+	/// with [`Account::complete_authorizations`], after which orders for the identifier will find it already authorized.
+	///
+	/// Wildcard identifiers cannot be pre-authorized.
+	///
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-7.4.1>
+	pub async fn pre_authorize(&mut self, identifier: &Identifier, challenge_types: &[ChallengeType]) -> anyhow::Result<Option<OrderPendingAuthorization>> {
+		#[derive(serde::Serialize)]
+		struct NewAuthorizationRequest<'a> {
+			identifier: &'a Identifier,
+		}
+
+		if matches!(identifier, Identifier::Dns(domain_name) if domain_name.starts_with("*.")) {
+			return Err(anyhow::anyhow!("cannot pre-authorize wildcard identifier {identifier}; wildcards can only be authorized through an order"));
+		}
+
+		let new_authz_url = self.new_authz_url.clone().context("ACME server does not support pre-authorization")?;
+
+		let (authorization_url, authorization) = self.logger.report_operation("acme/authorization", &identifier.to_string(), <log2::ScopedObjectOperation>::Get, async {
+			let http_common::ResponseWithLocation {
+				location: authorization_url,
+				body: authorization,
+			}: http_common::ResponseWithLocation<AuthorizationWithChallengesResponse> =
+				self.post(new_authz_url, Some(&NewAuthorizationRequest {
+					identifier,
+				})).await.context("could not create authorization")?;
+			Ok::<_, anyhow::Error>((authorization_url, authorization))
+		}).await?;
+
+		self.logger.report_state("acme/authorization", &authorization_url, format_args!("{authorization:?}"));
+
+		match authorization {
+			AuthorizationWithChallengesResponse::Pending { identifier, wildcard, challenges } =>
+				Ok(Some(self.pending_authorization(authorization_url, identifier, wildcard, challenges, challenge_types)?)),
+			AuthorizationWithChallengesResponse::Valid => Ok(None),
+			AuthorizationWithChallengesResponse::Failed(err) => Err(err.into()),
+		}
+	}
+
+	fn pending_authorization(
+		&self,
+		authorization_url: http_common::Uri,
+		identifier: Identifier,
+		wildcard: bool,
+		mut challenges: Vec<ChallengePending>,
+		challenge_types: &[ChallengeType],
+	) -> anyhow::Result<OrderPendingAuthorization> {
+		// A challenge that has already been triggered must be the one that is completed, regardless of preference.
+		let (
+			challenge_type,
+			ChallengePending { token, r#type: _, url: http_common::DeserializableUri(challenge_url), issuer_domain_names, triggered: challenge_triggered },
+		) =
+			challenges.iter()
+			.filter(|challenge| challenge.triggered)
+			.find_map(|challenge| challenge_types.iter().copied().find(|challenge_type| challenge.r#type == challenge_type.name()))
+			.into_iter()
+			.chain(challenge_types.iter().copied())
+			.find_map(|challenge_type| {
+				let i = challenges.iter().position(|challenge| challenge.r#type == challenge_type.name())?;
+				Some((challenge_type, challenges.swap_remove(i)))
+			})
+			.with_context(|| format!(
+				"did not find any pending {:?} challenges for {identifier}",
+				challenge_types.iter().map(|challenge_type| challenge_type.name()).collect::<Vec<_>>(),
+			))?;
+
+		let key_authorization = || {
+			let token = token.as_deref().with_context(|| format!("{} challenge for {identifier} does not have a token", challenge_type.name()))?;
+			Ok::<_, anyhow::Error>(key_authorization(token, &self.account_key.as_jwk()))
+		};
+
+		let challenge = match challenge_type {
+			ChallengeType::Dns01 => PendingChallenge::Dns01 {
+				dns_txt_record_content: dns_01_txt_record_content(&key_authorization()?),
+			},
+
+			ChallengeType::DnsAccount01 => {
+				let account_url = self.account_url.as_deref().context("account URL is not known")?;
+				PendingChallenge::DnsAccount01 {
+					dns_txt_record_label: dns_account_01_txt_record_label(account_url),
+					dns_txt_record_content: dns_01_txt_record_content(&key_authorization()?),
+				}
+			},
+
+			ChallengeType::DnsPersist01 => {
+				let account_url = self.account_url.as_deref().context("account URL is not known")?;
+				let issuer_domain_name =
+					issuer_domain_names.first()
+					.with_context(|| format!("dns-persist-01 challenge for {identifier} does not have any issuer domain names"))?;
+				PendingChallenge::DnsPersist01 {
+					dns_txt_record_content: dns_persist_01_txt_record_content(issuer_domain_name, account_url, wildcard),
+				}
+			},
+
+			ChallengeType::Http01 => PendingChallenge::Http01 {
+				key_authorization: key_authorization()?,
+				token: token.context("http-01 challenge does not have a token")?,
+			},
+
+			ChallengeType::TlsAlpn01 => {
+				let (certificate_der, private_key_der) = tls_alpn_01::certificate(&identifier, &key_authorization()?)?;
+				PendingChallenge::TlsAlpn01 {
+					certificate_der,
+					private_key_der,
+				}
+			},
+		};
+
+		Ok(OrderPendingAuthorization {
+			authorization_url,
+			challenge_url,
+			challenge_triggered,
+			identifier,
+			challenge,
+		})
+	}
+
+	/// The order is borrowed rather than consumed so that its authorizations can be deactivated with [`Account::deactivate_authorization`]
+	/// if this fails.
 	pub async fn complete_authorization(
 		&mut self,
 		OrderPending {
 			order_url,
 			authorizations,
-		}: OrderPending,
+		}: &OrderPending,
 	) -> anyhow::Result<OrderReady> {
+		self.complete_authorizations(authorizations).await?;
+
+		Ok(OrderReady {
+			order_url: order_url.clone(),
+		})
+	}
+
+	/// Completes the challenges of the given authorizations and waits for the authorizations to become valid.
+	pub async fn complete_authorizations(&mut self, authorizations: &[OrderPendingAuthorization]) -> anyhow::Result<()> {
 		#[derive(serde::Serialize)]
 		struct ChallengeCompleteRequest { }
 
@@ -404,6 +698,7 @@ impl<K> Account<'_, K> where K: AccountKey {
 			Pending,
 			Processing { retry_after: std::time::Duration },
 			Valid,
+			Invalid { error: Option<Problem> },
 		}
 
 		impl http_common::FromResponse for ChallengeResponse {
@@ -416,12 +711,14 @@ impl<K> Account<'_, K> where K: AccountKey {
 					(http_common::StatusCode::OK, Some(body)) => Some(match body.as_json()? {
 						Challenge::Pending(serde::de::IgnoredAny) => ChallengeResponse::Pending,
 
-						Challenge::Processing => {
+						Challenge::Processing(serde::de::IgnoredAny) => {
 							let retry_after = http_common::get_retry_after(&headers, std::time::Duration::from_secs(1), std::time::Duration::from_secs(30))?;
 							ChallengeResponse::Processing { retry_after }
 						},
 
 						Challenge::Valid => ChallengeResponse::Valid,
+
+						Challenge::Invalid { error } => ChallengeResponse::Invalid { error },
 					}),
 					_ => None,
 				})
@@ -432,6 +729,7 @@ impl<K> Account<'_, K> where K: AccountKey {
 		enum AuthorizationResponse {
 			Pending { retry_after: std::time::Duration },
 			Valid,
+			Failed(StatusError),
 		}
 
 		impl http_common::FromResponse for AuthorizationResponse {
@@ -448,6 +746,11 @@ impl<K> Account<'_, K> where K: AccountKey {
 						},
 
 						Authorization::Valid => AuthorizationResponse::Valid,
+
+						Authorization::Invalid(failed) => AuthorizationResponse::Failed(failed.into_status_error("invalid")),
+						Authorization::Deactivated(failed) => AuthorizationResponse::Failed(failed.into_status_error("deactivated")),
+						Authorization::Expired(failed) => AuthorizationResponse::Failed(failed.into_status_error("expired")),
+						Authorization::Revoked(failed) => AuthorizationResponse::Failed(failed.into_status_error("revoked")),
 					}),
 
 					_ => None,
@@ -455,56 +758,149 @@ impl<K> Account<'_, K> where K: AccountKey {
 			}
 		}
 
-		for OrderPendingAuthorization {
-			authorization_url,
-			challenge_url,
-			dns_txt_record_content: _,
-		} in authorizations {
-			self.logger.report_message(format_args!("Completing challenge {challenge_url} ..."));
+		let this = &*self;
 
-			let mut body = Some(&ChallengeCompleteRequest { });
-			loop {
-				let challenge = self.post(challenge_url.clone(), body.take()).await.context("could not complete challenge")?;
+		let deadline = this.phase_deadline();
 
-				self.logger.report_state("acme/challenge", &challenge_url, format_args!("{challenge:?}"));
+		// Trigger all challenges first so that the ACME server can validate them in parallel.
+		//
+		// Challenges that were already triggered are only polled, since they can't be triggered again.
+		let challenges = futures_util::future::try_join_all(authorizations.iter().map(|OrderPendingAuthorization { challenge_url, challenge_triggered, .. }| async move {
+			let challenge: ChallengeResponse =
+				if *challenge_triggered {
+					this.logger.report_message(format_args!("Challenge {challenge_url} is already triggered ..."));
+					this.post(challenge_url.clone(), None::<&()>).await.context("could not get challenge")?
+				}
+				else {
+					this.logger.report_message(format_args!("Completing challenge {challenge_url} ..."));
+					this.post(challenge_url.clone(), Some(&ChallengeCompleteRequest { })).await.context("could not complete challenge")?
+				};
+			Ok::<_, anyhow::Error>(challenge)
+		})).await?;
+
+		futures_util::future::try_join_all(authorizations.iter().zip(challenges).map(|(
+			OrderPendingAuthorization {
+				authorization_url,
+				challenge_url,
+				challenge_triggered: _,
+				identifier,
+				challenge: _,
+			},
+			mut challenge,
+		)| async move {
+			loop {
+				this.logger.report_state("acme/challenge", challenge_url, format_args!("{challenge:?}"));
 
 				match challenge {
 					ChallengeResponse::Pending => {
 						let retry_after = std::time::Duration::from_secs(1);
-						self.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking challenge..."));
-						tokio::time::sleep(retry_after).await;
+						this.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking challenge {challenge_url} ..."));
+						this.sleep_before_polling(deadline, retry_after, "acme/challenge", challenge_url, &challenge).await?;
 					},
 
 					ChallengeResponse::Processing { retry_after } => {
-						self.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking challenge..."));
-						tokio::time::sleep(retry_after).await;
+						this.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking challenge {challenge_url} ..."));
+						this.sleep_before_polling(deadline, retry_after, "acme/challenge", challenge_url, &challenge).await?;
 					},
 
 					ChallengeResponse::Valid => break,
+
+					ChallengeResponse::Invalid { error } => return Err(StatusError {
+						object_type: "challenge",
+						identifier: Some(identifier.clone()),
+						status: "invalid",
+						problem: error,
+					}.into()),
 				}
+
+				challenge = this.post(challenge_url.clone(), None::<&()>).await.context("could not get challenge")?;
 			}
 
-			self.logger.report_message(format_args!("Waiting for authorization {authorization_url} ..."));
+			this.logger.report_message(format_args!("Waiting for authorization {authorization_url} ..."));
 
 			loop {
-				let authorization = self.post(authorization_url.clone(), None::<&()>).await.context("could not get authorization")?;
+				let authorization: AuthorizationResponse = this.post(authorization_url.clone(), None::<&()>).await.context("could not get authorization")?;
 
-				self.logger.report_state("acme/authorization", &authorization_url, format_args!("{authorization:?}"));
+				this.logger.report_state("acme/authorization", authorization_url, format_args!("{authorization:?}"));
 
 				match authorization {
 					AuthorizationResponse::Pending { retry_after } => {
-						self.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking authorization..."));
-						tokio::time::sleep(retry_after).await;
+						this.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking authorization {authorization_url} ..."));
+						this.sleep_before_polling(deadline, retry_after, "acme/authorization", authorization_url, &authorization).await?;
 					},
 
 					AuthorizationResponse::Valid => break,
+
+					AuthorizationResponse::Failed(err) => return Err(err.into()),
 				}
 			}
+
+			Ok::<_, anyhow::Error>(())
+		})).await?;
+
+		Ok(())
+	}
+
+	/// Deactivates the authorization if it is still pending, so that it no longer counts towards the ACME server's limit of pending authorizations.
+	///
+	/// Authorizations that have already become valid are left alone so that they can be reused by later orders.
+	/// Returns whether the authorization was deactivated.
+	///
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-7.5.2>
+	pub async fn deactivate_authorization(&mut self, authorization: &OrderPendingAuthorization) -> anyhow::Result<bool> {
+		#[derive(serde::Serialize)]
+		struct DeactivateAuthorizationRequest {
+			status: &'static str,
 		}
 
-		Ok(OrderReady {
-			order_url,
-		})
+		#[derive(Debug)]
+		struct AuthorizationStatusResponse {
+			status: &'static str,
+		}
+
+		impl http_common::FromResponse for AuthorizationStatusResponse {
+			fn from_response(
+				status: http_common::StatusCode,
+				body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+				_headers: http_common::HeaderMap,
+			) -> anyhow::Result<Option<Self>> {
+				Ok(match (status, body) {
+					(http_common::StatusCode::OK, Some(body)) => {
+						let status = match body.as_json()? {
+							Authorization::Pending(serde::de::IgnoredAny) => "pending",
+							Authorization::Valid => "valid",
+							Authorization::Invalid(_) => "invalid",
+							Authorization::Deactivated(_) => "deactivated",
+							Authorization::Expired(_) => "expired",
+							Authorization::Revoked(_) => "revoked",
+						};
+						Some(AuthorizationStatusResponse { status })
+					},
+
+					_ => None,
+				})
+			}
+		}
+
+		let AuthorizationStatusResponse { status } = self.post(authorization.authorization_url.clone(), None::<&()>).await.context("could not get authorization")?;
+		if status != "pending" {
+			self.logger.report_state("acme/authorization", &authorization.authorization_url, format_args!("not deactivated because it has {status} status"));
+			return Ok(false);
+		}
+
+		let AuthorizationStatusResponse { status } = self.logger.report_operation("acme/authorization", &authorization.authorization_url, <log2::ScopedObjectOperation>::Delete, async {
+			let response =
+				self.post(authorization.authorization_url.clone(), Some(&DeactivateAuthorizationRequest {
+					status: "deactivated",
+				})).await.context("could not deactivate authorization")?;
+			Ok::<_, anyhow::Error>(response)
+		}).await?;
+
+		if status != "deactivated" {
+			return Err(anyhow::anyhow!("authorization for {} has {status} status", authorization.identifier));
+		}
+
+		Ok(true)
 	}
 
 	pub async fn finalize_order(
@@ -522,20 +918,22 @@ impl<K> Account<'_, K> where K: AccountKey {
 
 		self.logger.report_message(format_args!("Finalizing order {order_url} ..."));
 
+		let deadline = self.phase_deadline();
+
 		let order = loop {
-			let order = self.post(order_url.clone(), None::<&()>).await.context("could not get order")?;
+			let order: OrderResponse<serde::de::IgnoredAny, OrderObjReady> = self.post(order_url.clone(), None::<&()>).await.context("could not get order")?;
 
 			self.logger.report_state("acme/order", &order_url, format_args!("{order:?}"));
 
-			match order {
-				OrderResponse::Pending(serde::de::IgnoredAny) => return Err(anyhow::anyhow!("order is still pending")),
+			match order.status {
+				OrderStatus::Pending(serde::de::IgnoredAny) => return Err(anyhow::anyhow!("order is still pending")),
 
-				OrderResponse::Processing { retry_after } => {
+				OrderStatus::Processing { retry_after } => {
 					self.logger.report_message(format_args!("Waiting for {retry_after:?} before rechecking order..."));
-					tokio::time::sleep(retry_after).await;
+					self.sleep_before_polling(deadline, retry_after, "acme/order", &order_url, &order).await?;
 				},
 
-				OrderResponse::Ready(OrderObjReady { finalize_url: http_common::DeserializableUri(finalize_url) }) => {
+				OrderStatus::Ready(OrderObjReady { finalize_url: http_common::DeserializableUri(finalize_url) }) => {
 					#[derive(serde::Serialize)]
 					struct FinalizeOrderRequest<'a> {
 						csr: &'a str,
@@ -560,69 +958,297 @@ impl<K> Account<'_, K> where K: AccountKey {
 						self.post(finalize_url, Some(&FinalizeOrderRequest { csr })).await.context("could not finalize order")?;
 				},
 
-				OrderResponse::Valid { certificate_url } => break OrderValid {
+				OrderStatus::Valid { certificate_url } => break OrderValid {
+					order_url,
 					certificate_url,
 				},
+
+				OrderStatus::Invalid { error } => return Err(StatusError::order(error).into()),
 			}
 		};
 
 		Ok(order)
 	}
 
+	/// Downloads the certificate chain of the order.
+	///
+	/// If `preferred_chain` is set, the default chain and the alternate chains offered by the ACME server are searched for a chain
+	/// whose topmost certificate is issued by a CA with that common name. If none of them match, the default chain is returned.
 	pub async fn download_certificate(
 		&mut self,
 		OrderValid {
+			order_url: _,
 			certificate_url,
 		}: OrderValid,
+		preferred_chain: Option<&str>,
 	) -> anyhow::Result<String> {
-		struct CertificateResponse(String);
+		let (certificate, alternate_urls) = self.get_certificate_chain(certificate_url).await?;
+
+		let Some(preferred_chain) = preferred_chain else { return Ok(certificate); };
+
+		if top_certificate_issuer_matches(&certificate, preferred_chain)? {
+			return Ok(certificate);
+		}
+
+		for alternate_url in alternate_urls {
+			let (alternate_certificate, _) = self.get_certificate_chain(alternate_url).await?;
+			if top_certificate_issuer_matches(&alternate_certificate, preferred_chain)? {
+				return Ok(alternate_certificate);
+			}
+		}
+
+		self.logger.report_message(format_args!("No certificate chain matches preferred chain {preferred_chain:?}, using default chain"));
+
+		Ok(certificate)
+	}
+
+	/// Returns the certificate chain at the given URL, and the URLs of alternate chains from its `Link: <...>;rel="alternate"` headers.
+	///
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-7.4.2>
+	async fn get_certificate_chain(&mut self, certificate_url: http_common::Uri) -> anyhow::Result<(String, Vec<http_common::Uri>)> {
+		#[derive(Debug)]
+		struct CertificateResponse {
+			certificate: String,
+			alternate_urls: Vec<http_common::Uri>,
+		}
 
 		impl http_common::FromResponse for CertificateResponse {
 			fn from_response(
 				status: http_common::StatusCode,
 				body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
-				_headers: http_common::HeaderMap,
+				headers: http_common::HeaderMap,
 			) -> anyhow::Result<Option<Self>> {
 				Ok(match (status, body) {
 					(http_common::StatusCode::OK, Some(body)) => {
 						let certificate = body.as_str("application/pem-certificate-chain")?.into_owned();
-						Some(CertificateResponse(certificate))
+
+						let mut alternate_urls = vec![];
+						for link in headers.get_all(http_common::LINK) {
+							let link = link.to_str().context("could not parse link header")?;
+							for link in link.split(',') {
+								let Some((url, params)) = link.trim().strip_prefix('<').and_then(|link| link.split_once('>')) else { continue; };
+								let is_alternate =
+									params.split(';')
+									.any(|param| matches!(param.trim(), r#"rel="alternate""# | "rel=alternate"));
+								if is_alternate {
+									alternate_urls.push(url.try_into().context("could not parse alternate certificate chain URL")?);
+								}
+							}
+						}
+
+						Some(CertificateResponse { certificate, alternate_urls })
 					},
 					_ => None,
 				})
 			}
 		}
 
-		let certificate = self.logger.report_operation("acme/certificate", &certificate_url.clone(), <log2::ScopedObjectOperation>::Get, async {
-			let CertificateResponse(certificate) = self.post(certificate_url, None::<&()>).await.context("could not download certificate")?;
-			Ok::<_, anyhow::Error>(certificate)
-		}).await?;
+		let CertificateResponse { certificate, alternate_urls } =
+			self.logger.report_operation("acme/certificate", &certificate_url.clone(), <log2::ScopedObjectOperation>::Get, async {
+				let response = self.post(certificate_url, None::<&()>).await.context("could not download certificate")?;
+				Ok::<_, anyhow::Error>(response)
+			}).await?;
 
-		Ok(certificate)
+		Ok((certificate, alternate_urls))
 	}
 
-	async fn post<TRequest, TResponse>(
-		&mut self,
-		url: http_common::Uri,
-		body: Option<&TRequest>,
-	) -> anyhow::Result<TResponse>
-	where
-		TRequest: serde::Serialize,
-		TResponse: http_common::FromResponse,
-	{
-		// This fn encapsulates the non-generic parts of `post` to reduce code size from monomorphization.
-		async fn make_request<K>(account: &mut Account<'_, K>, url: http_common::Uri, payload: Vec<u8>) -> anyhow::Result<http_common::Request<http_common::RequestBody>> where K: AccountKey {
-			#[allow(clippy::declare_interior_mutable_const)] // Clippy doesn't like const http_common::HeaderValue
-			const APPLICATION_JOSE_JSON: http_common::HeaderValue = http_common::HeaderValue::from_static("application/jose+json");
+	pub async fn revoke_certificate(&mut self, certificate_der: &[u8], reason: Option<RevocationReason>) -> anyhow::Result<()> {
+		#[derive(serde::Serialize)]
+		struct RevokeCertRequest {
+			certificate: String,
 
-			let nonce =
-				if let Some(nonce) = account.nonce.take() {
-					nonce
-				}
-				else {
-					struct NewNonceResponse;
+			#[serde(skip_serializing_if = "Option::is_none")]
+			reason: Option<u8>,
+		}
 
-					impl http_common::FromResponse for NewNonceResponse {
+		struct RevokeCertResponse;
+
+		impl http_common::FromResponse for RevokeCertResponse {
+			fn from_response(
+				status: http_common::StatusCode,
+				_body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+				_headers: http_common::HeaderMap,
+			) -> anyhow::Result<Option<Self>> {
+				Ok(match status {
+					http_common::StatusCode::OK => Some(RevokeCertResponse),
+					_ => None,
+				})
+			}
+		}
+
+		let certificate = base64::Engine::encode(&JWS_BASE64_ENGINE, certificate_der);
+
+		self.logger.report_message(format_args!("Revoking certificate with reason {reason:?} ..."));
+
+		self.logger.report_operation("acme/certificate", "", <log2::ScopedObjectOperation>::Delete, async {
+			let RevokeCertResponse =
+				self.post(self.revoke_cert_url.clone(), Some(&RevokeCertRequest {
+					certificate,
+					reason: reason.map(RevocationReason::code),
+				})).await.context("could not revoke certificate")?;
+			Ok::<_, anyhow::Error>(())
+		}).await?;
+
+		Ok(())
+	}
+
+	/// Rolls over the account to `new_key`.
+	///
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-7.3.5>
+	pub async fn change_key<'b>(self, new_key: &'b K) -> anyhow::Result<Account<'b, K>> where 'a: 'b {
+		#[derive(serde::Serialize)]
+		struct KeyChangeProtected<'a> {
+			alg: &'a str,
+			jwk: Jwk<'a>,
+			url: std::fmt::Arguments<'a>,
+		}
+
+		#[derive(serde::Serialize)]
+		struct KeyChangePayload<'a> {
+			account: &'a str,
+
+			#[serde(rename = "oldKey")]
+			old_key: Jwk<'a>,
+		}
+
+		struct KeyChangeResponse;
+
+		impl http_common::FromResponse for KeyChangeResponse {
+			fn from_response(
+				status: http_common::StatusCode,
+				_body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+				_headers: http_common::HeaderMap,
+			) -> anyhow::Result<Option<Self>> {
+				Ok(match status {
+					http_common::StatusCode::OK => Some(KeyChangeResponse),
+					_ => None,
+				})
+			}
+		}
+
+		let account = self;
+
+		let account_url = account.account_url.clone().context("account URL is not known")?;
+
+		account.logger.report_operation("acme/account/key", &account_url.clone(), log2::ScopedObjectOperation::Create { value: "" }, async {
+			let new_jwk = new_key.as_jwk();
+
+			let protected =
+				serialize_base64_json(&KeyChangeProtected {
+					alg: new_jwk.jws_sign_alg(),
+					jwk: new_jwk,
+					url: format_args!("{}", account.key_change_url),
+				}).context("could not serialize key change `protected`")?;
+
+			let payload =
+				serialize_base64_json(&KeyChangePayload {
+					account: &account_url,
+					old_key: account.account_key.as_jwk(),
+				}).context("could not serialize key change `payload`")?;
+
+			let signature =
+				new_key.sign([
+					&protected,
+					&b"."[..],
+					&payload,
+				]).await?;
+
+			let KeyChangeResponse =
+				account.post(account.key_change_url.clone(), Some(&Jws {
+					payload: String::from_utf8(payload).expect("base64 is valid UTF-8"),
+					protected: String::from_utf8(protected).expect("base64 is valid UTF-8"),
+					signature,
+				})).await.context("could not change account key")?;
+			Ok::<_, anyhow::Error>(())
+		}).await?;
+
+		let Account {
+			inner,
+			key_change_url,
+			new_authz_url,
+			new_nonce_url,
+			new_order_url,
+			revoke_cert_url,
+			profiles,
+			logger,
+			account_key: _,
+			account_url,
+			contact_urls,
+			polling_phase_timeout,
+			polling_deadline,
+			nonce,
+		} = account;
+
+		Ok(Account {
+			inner,
+			key_change_url,
+			new_authz_url,
+			new_nonce_url,
+			new_order_url,
+			revoke_cert_url,
+			profiles,
+			logger,
+			account_key: new_key,
+			account_url,
+			contact_urls,
+			polling_phase_timeout,
+			polling_deadline,
+			nonce,
+		})
+	}
+
+	/// The deadline for a polling phase that starts now.
+	fn phase_deadline(&self) -> Option<tokio::time::Instant> {
+		let phase_deadline = self.polling_phase_timeout.map(|phase_timeout| tokio::time::Instant::now() + phase_timeout);
+		match (phase_deadline, self.polling_deadline) {
+			(Some(phase_deadline), Some(polling_deadline)) => Some(phase_deadline.min(polling_deadline)),
+			(phase_deadline, polling_deadline) => phase_deadline.or(polling_deadline),
+		}
+	}
+
+	/// Sleeps for `retry_after` before the object is polled again, or fails if that would go past `deadline`.
+	async fn sleep_before_polling(
+		&self,
+		deadline: Option<tokio::time::Instant>,
+		retry_after: std::time::Duration,
+		object_type: &'static str,
+		object_url: &http_common::Uri,
+		last_state: &dyn std::fmt::Debug,
+	) -> anyhow::Result<()> {
+		if let Some(deadline) = deadline {
+			if tokio::time::Instant::now() + retry_after > deadline {
+				self.logger.report_state(object_type, object_url, format_args!("timed out in state {last_state:?}"));
+				return Err(anyhow::anyhow!("timed out waiting for {object_type} {object_url}"));
+			}
+		}
+
+		tokio::time::sleep(retry_after).await;
+		Ok(())
+	}
+
+	async fn post<TRequest, TResponse>(
+		&self,
+		url: http_common::Uri,
+		body: Option<&TRequest>,
+	) -> anyhow::Result<TResponse>
+	where
+		TRequest: serde::Serialize,
+		TResponse: http_common::FromResponse,
+	{
+		// This fn encapsulates the non-generic parts of `post` to reduce code size from monomorphization.
+		async fn make_request<K>(account: &Account<'_, K>, url: http_common::Uri, payload: http_common::Bytes) -> anyhow::Result<http_common::Request<http_common::RequestBody>> where K: AccountKey {
+			#[allow(clippy::declare_interior_mutable_const)] // Clippy doesn't like const http_common::HeaderValue
+			const APPLICATION_JOSE_JSON: http_common::HeaderValue = http_common::HeaderValue::from_static("application/jose+json");
+
+			let nonce = account.nonce.lock().expect("nonce mutex is not poisoned").take();
+			let nonce =
+				if let Some(nonce) = nonce {
+					nonce
+				}
+				else {
+					struct NewNonceResponse;
+
+					impl http_common::FromResponse for NewNonceResponse {
 						fn from_response(
 							status: http_common::StatusCode,
 							_body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
@@ -640,10 +1266,11 @@ impl<K> Account<'_, K> where K: AccountKey {
 						*req.method_mut() = http_common::Method::HEAD;
 						*req.uri_mut() = account.new_nonce_url.clone();
 
-						let ResponseWithNewNonce::<NewNonceResponse> { body: _, new_nonce } =
+						let ResponseWithNewNonce::<NewNonceResponse> { body, new_nonce } =
 							account.inner.request(req).await
 							.context("could not execute HTTP request")
 							.context("newNonce URL did not return new nonce")?;
+						let NewNonceResponse = body.context("newNonce URL did not return new nonce")?;
 
 						let nonce = new_nonce.context("newNonce URL did not return new nonce")?;
 						Ok::<_, anyhow::Error>(log2::Secret(nonce))
@@ -680,29 +1307,23 @@ impl<K> Account<'_, K> where K: AccountKey {
 				}
 
 				let jwk = account.account_key.as_jwk();
-				let alg = jwk.crv.jws_sign_alg();
+				let alg = jwk.jws_sign_alg();
 
 				let jwk_or_kid = account.account_url.as_deref().map_or_else(|| JwkOrKid::Jwk(jwk), JwkOrKid::Kid);
 
-				let mut writer = base64::write::EncoderWriter::new(Vec::with_capacity(1024), &JWS_BASE64_ENGINE);
-				let mut serializer = serde_json::Serializer::new(&mut writer);
-				serde::Serialize::serialize(
-					&Protected {
-						alg,
-						jwk_or_kid,
-						nonce: &nonce,
-						url: format_args!("{url}"),
-					},
-					&mut serializer,
-				).context("could not serialize `protected`")?;
-				writer.finish().expect("cannot fail to write to Vec<u8>")
+				serialize_base64_json(&Protected {
+					alg,
+					jwk_or_kid,
+					nonce: &nonce,
+					url: format_args!("{url}"),
+				}).context("could not serialize `protected`")?
 			};
 
 			let signature =
 				account.account_key.sign([
 					&protected,
 					&b"."[..],
-					&payload,
+					&payload[..],
 				]).await?;
 
 			// All strings are base64 so there's no need to get serde_json involved
@@ -714,7 +1335,7 @@ impl<K> Account<'_, K> where K: AccountKey {
 
 				let body = [
 					PART1,
-					payload.into(),
+					payload,
 					PART2,
 					protected.into(),
 					PART3,
@@ -732,43 +1353,247 @@ impl<K> Account<'_, K> where K: AccountKey {
 			Ok(req)
 		}
 
+		const MAX_BAD_NONCE_RETRIES: usize = 3;
+
 		let payload =
 			if let Some(payload) = body {
-				let mut writer = base64::write::EncoderWriter::new(vec![], &JWS_BASE64_ENGINE);
-				let mut serializer = serde_json::Serializer::new(&mut writer);
-				serde::Serialize::serialize(payload, &mut serializer).context("could not serialize `payload`")?;
-				writer.finish().expect("cannot fail to write to Vec<u8>")
+				serialize_base64_json(payload).context("could not serialize `payload`")?
 			}
 			else {
 				vec![]
 			};
 
-		let req = make_request(self, url, payload).await?;
+		let payload: http_common::Bytes = payload.into();
+
+		let mut bad_nonce_retries_remaining = MAX_BAD_NONCE_RETRIES;
+
+		loop {
+			let req = make_request(self, url.clone(), payload.clone()).await?;
+
+			let ResponseWithNewNonce { body, new_nonce } =
+				self.inner.request(req).await.context("could not execute HTTP request")?;
+
+			*self.nonce.lock().expect("nonce mutex is not poisoned") = new_nonce;
+
+			match body {
+				Ok(body) => break Ok(body),
+
+				// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-6.5>
+				//
+				// The error response contains a fresh nonce, so retry with that.
+				Err(problem) if problem.r#type == ProblemType::BadNonce && bad_nonce_retries_remaining > 0 => {
+					self.logger.report_message(format_args!("Retrying request to {url} after {problem}"));
+					bad_nonce_retries_remaining -= 1;
+				},
+
+				Err(problem) => break Err(problem.into()),
+			}
+		}
+	}
+}
+
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-7.3.4>
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-7.1.1>
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub struct DirectoryMeta {
+	/// The URL of the current terms of service.
+	#[serde(rename = "termsOfService")]
+	pub terms_of_service: Option<String>,
+
+	pub website: Option<String>,
+
+	/// The domain names that the ACME server recognizes as referring to itself in CAA records.
+	#[serde(default, rename = "caaIdentities")]
+	pub caa_identities: Vec<String>,
+
+	#[serde(default, rename = "externalAccountRequired")]
+	pub external_account_required: bool,
+
+	/// Ref: <https://datatracker.ietf.org/doc/draft-ietf-acme-profiles/>
+	#[serde(default)]
+	pub profiles: std::collections::BTreeMap<String, String>,
+}
+
+pub struct ExternalAccountBinding<'a> {
+	/// The key identifier provided by the CA.
+	pub key_id: &'a str,
+
+	/// The base64url-encoded HMAC key provided by the CA.
+	pub hmac_key: &'a str,
+}
+
+impl ExternalAccountBinding<'_> {
+	fn sign(&self, jwk: &Jwk<'_>, new_account_url: &http_common::Uri) -> anyhow::Result<Jws> {
+		#[derive(serde::Serialize)]
+		struct Protected<'a> {
+			alg: &'a str,
+			kid: &'a str,
+			url: std::fmt::Arguments<'a>,
+		}
+
+		let hmac_key =
+			base64::Engine::decode(&JWS_BASE64_ENGINE, self.hmac_key.trim_end_matches('='))
+			.context("could not decode external account binding HMAC key")?;
+
+		let protected =
+			serialize_base64_json(&Protected {
+				alg: "HS256",
+				kid: self.key_id,
+				url: format_args!("{new_account_url}"),
+			}).context("could not serialize external account binding `protected`")?;
+
+		let payload = serialize_base64_json(jwk).context("could not serialize external account binding `payload`")?;
+
+		let signature = {
+			let mut signer: hmac::Hmac<sha2::Sha256> = hmac::Mac::new_from_slice(&hmac_key).expect("cannot fail to create hmac::Hmac<sha2::Sha256>");
+			hmac::Mac::update(&mut signer, &protected);
+			hmac::Mac::update(&mut signer, b".");
+			hmac::Mac::update(&mut signer, &payload);
+			let signature = hmac::Mac::finalize(signer).into_bytes();
+			base64::Engine::encode(&JWS_BASE64_ENGINE, signature)
+		};
+
+		Ok(Jws {
+			payload: String::from_utf8(payload).expect("base64 is valid UTF-8"),
+			protected: String::from_utf8(protected).expect("base64 is valid UTF-8"),
+			signature,
+		})
+	}
+}
+
+/// A JWS in flattened JSON serialization, for embedding in a request payload.
+#[derive(serde::Serialize)]
+struct Jws {
+	payload: String,
+	protected: String,
+	signature: String,
+}
+
+#[derive(serde::Serialize)]
+struct NewAccountRequest<'a> {
+	#[serde(rename = "contact", skip_serializing_if = "Option::is_none")]
+	contact_urls: Option<&'a [&'a str]>,
+
+	#[serde(rename = "externalAccountBinding", skip_serializing_if = "Option::is_none")]
+	external_account_binding: Option<Jws>,
+
+	#[serde(rename = "onlyReturnExisting", skip_serializing_if = "std::ops::Not::not")]
+	only_return_existing: bool,
+
+	#[serde(rename = "termsOfServiceAgreed", skip_serializing_if = "Option::is_none")]
+	terms_of_service_agreed: Option<bool>,
+}
 
-		let ResponseWithNewNonce { body, new_nonce } =
-			self.inner.request(req).await.context("could not execute HTTP request")?;
+#[derive(Debug, serde::Deserialize)]
+struct AccountResponse {
+	status: AccountStatus,
 
-		self.nonce = new_nonce;
+	#[serde(default, rename = "contact")]
+	contact_urls: Vec<String>,
+}
 
-		Ok(body)
+impl http_common::FromResponse for AccountResponse {
+	fn from_response(
+		status: http_common::StatusCode,
+		body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+		_headers: http_common::HeaderMap,
+	) -> anyhow::Result<Option<Self>> {
+		Ok(match (status, body) {
+			(http_common::StatusCode::CREATED | http_common::StatusCode::OK, Some(body)) => Some(body.as_json()?),
+			_ => None,
+		})
 	}
 }
 
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+enum AccountStatus {
+	#[serde(rename = "deactivated")]
+	Deactivated,
+	#[serde(rename = "revoked")]
+	Revoked,
+	#[serde(rename = "valid")]
+	Valid,
+}
+
 pub trait AccountKey {
 	fn as_jwk(&self) -> Jwk<'_>;
 
+	/// Signs the concatenation of the given byte strings with the [`Jwk::jws_sign_alg`] algorithm of this key's JWK,
+	/// and returns the base64url-encoded signature.
 	fn sign<I>(&self, digest: I) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<String>> + '_>>
 	where
 		I: IntoIterator,
 		<I as IntoIterator>::Item: AsRef<[u8]>;
 }
 
-#[derive(Clone, Copy, serde::Serialize)]
-pub struct Jwk<'a> {
-	pub crv: EcCurve,
-	pub kty: &'a str,
-	pub x: &'a str,
-	pub y: &'a str,
+/// The public part of an account key. All members are base64url-encoded.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc7518#section-6>
+#[derive(Clone, Copy)]
+pub enum Jwk<'a> {
+	Ec {
+		crv: EcCurve,
+		x: &'a str,
+		y: &'a str,
+	},
+
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8037#section-2>
+	Okp {
+		crv: OkpCurve,
+		x: &'a str,
+	},
+
+	Rsa {
+		e: &'a str,
+		n: &'a str,
+	},
+}
+
+impl Jwk<'_> {
+	pub const fn jws_sign_alg(&self) -> &'static str {
+		match self {
+			Jwk::Ec { crv, .. } => crv.jws_sign_alg(),
+			Jwk::Okp { crv: OkpCurve::Ed25519, .. } => "EdDSA",
+			Jwk::Rsa { .. } => "RS256",
+		}
+	}
+}
+
+impl serde::Serialize for Jwk<'_> {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+		use serde::ser::SerializeMap;
+
+		// The JWK thumbprint is computed over the serialized JWK, which requires that the required members are serialized
+		// in lexicographic order and no other members are serialized.
+		//
+		// Ref: <https://www.rfc-editor.org/rfc/rfc7638#section-3>
+		match self {
+			Jwk::Ec { crv, x, y } => {
+				let mut serializer = serializer.serialize_map(Some(4))?;
+				serializer.serialize_entry("crv", crv)?;
+				serializer.serialize_entry("kty", "EC")?;
+				serializer.serialize_entry("x", x)?;
+				serializer.serialize_entry("y", y)?;
+				serializer.end()
+			},
+
+			Jwk::Okp { crv, x } => {
+				let mut serializer = serializer.serialize_map(Some(3))?;
+				serializer.serialize_entry("crv", crv)?;
+				serializer.serialize_entry("kty", "OKP")?;
+				serializer.serialize_entry("x", x)?;
+				serializer.end()
+			},
+
+			Jwk::Rsa { e, n } => {
+				let mut serializer = serializer.serialize_map(Some(3))?;
+				serializer.serialize_entry("e", e)?;
+				serializer.serialize_entry("kty", "RSA")?;
+				serializer.serialize_entry("n", n)?;
+				serializer.end()
+			},
+		}
+	}
 }
 
 #[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
@@ -793,12 +1618,171 @@ impl EcCurve {
 	}
 }
 
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+pub enum OkpCurve {
+	#[serde(rename = "Ed25519")]
+	Ed25519,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", content = "value")]
+pub enum Identifier {
+	#[serde(rename = "dns")]
+	Dns(String),
+
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8738>
+	#[serde(rename = "ip")]
+	Ip(std::net::IpAddr),
+}
+
+impl std::fmt::Display for Identifier {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Identifier::Dns(name) => f.write_str(name),
+			Identifier::Ip(addr) => addr.fmt(f),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize)]
+pub enum ChallengeType {
+	#[serde(rename = "dns-01")]
+	Dns01,
+
+	/// Ref: <https://datatracker.ietf.org/doc/draft-ietf-acme-dns-account-label/>
+	#[serde(rename = "dns-account-01")]
+	DnsAccount01,
+
+	/// Ref: <https://datatracker.ietf.org/doc/draft-ietf-acme-dns-persist/>
+	#[serde(rename = "dns-persist-01")]
+	DnsPersist01,
+
+	#[serde(rename = "http-01")]
+	Http01,
+
+	#[serde(rename = "tls-alpn-01")]
+	TlsAlpn01,
+}
+
+impl ChallengeType {
+	pub const fn name(self) -> &'static str {
+		match self {
+			ChallengeType::Dns01 => "dns-01",
+			ChallengeType::DnsAccount01 => "dns-account-01",
+			ChallengeType::DnsPersist01 => "dns-persist-01",
+			ChallengeType::Http01 => "http-01",
+			ChallengeType::TlsAlpn01 => "tls-alpn-01",
+		}
+	}
+}
+
+/// Ref: <https://www.rfc-editor.org/rfc/rfc5280#section-5.3.1>
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+pub enum RevocationReason {
+	#[serde(rename = "unspecified")]
+	Unspecified,
+
+	#[serde(rename = "keyCompromise")]
+	KeyCompromise,
+
+	#[serde(rename = "cACompromise")]
+	CaCompromise,
+
+	#[serde(rename = "affiliationChanged")]
+	AffiliationChanged,
+
+	#[serde(rename = "superseded")]
+	Superseded,
+
+	#[serde(rename = "cessationOfOperation")]
+	CessationOfOperation,
+
+	#[serde(rename = "certificateHold")]
+	CertificateHold,
+
+	#[serde(rename = "removeFromCRL")]
+	RemoveFromCrl,
+
+	#[serde(rename = "privilegeWithdrawn")]
+	PrivilegeWithdrawn,
+
+	#[serde(rename = "aACompromise")]
+	AaCompromise,
+}
+
+impl RevocationReason {
+	pub const fn code(self) -> u8 {
+		match self {
+			RevocationReason::Unspecified => 0,
+			RevocationReason::KeyCompromise => 1,
+			RevocationReason::CaCompromise => 2,
+			RevocationReason::AffiliationChanged => 3,
+			RevocationReason::Superseded => 4,
+			RevocationReason::CessationOfOperation => 5,
+			RevocationReason::CertificateHold => 6,
+			// 7 is unused
+			RevocationReason::RemoveFromCrl => 8,
+			RevocationReason::PrivilegeWithdrawn => 9,
+			RevocationReason::AaCompromise => 10,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct RenewalInfo {
+	pub suggested_window_start: time::OffsetDateTime,
+	pub suggested_window_end: time::OffsetDateTime,
+
+	/// A URL to a page with more information about the suggested window, such as why it was moved earlier.
+	pub explanation_url: Option<String>,
+
+	/// How long to wait before querying the renewal information again, if the server specified it.
+	pub retry_after: Option<std::time::Duration>,
+}
+
+impl RenewalInfo {
+	/// Returns a time inside the suggested window, so that renewals of many clients are spread out.
+	///
+	/// The time is derived from the certificate's ARI ID and the window, so it is uniformly distributed across certificates
+	/// but the same every time it is computed for the same window. A client that checks periodically and drew a new random time
+	/// on every check would be biased towards renewing at the start of the window.
+	pub fn renewal_time(&self, ari_id: &str) -> time::OffsetDateTime {
+		let start = self.suggested_window_start.unix_timestamp_nanos();
+		let end = self.suggested_window_end.unix_timestamp_nanos();
+
+		let mut hasher: sha2::Sha256 = sha2::Digest::new();
+		sha2::Digest::update(&mut hasher, ari_id);
+		sha2::Digest::update(&mut hasher, start.to_be_bytes());
+		sha2::Digest::update(&mut hasher, end.to_be_bytes());
+		let hash = sha2::Digest::finalize(hasher);
+		let hash = u64::from_be_bytes(hash[..8].try_into().expect("SHA-256 hash is at least 8 bytes"));
+
+		// The window is clamped to u64::MAX nanoseconds (about 584 years) so that the multiplication below cannot overflow.
+		let window = u128::try_from(end.saturating_sub(start).clamp(0, u64::MAX.into())).expect("clamped window fits in u128");
+		// Multiply-and-shift maps the hash uniformly onto [0, window] without the bias of `%`.
+		let offset = ((window + 1) * u128::from(hash)) >> 64;
+		let renewal_time = start.saturating_add(i128::try_from(offset).unwrap_or(0));
+		time::OffsetDateTime::from_unix_timestamp_nanos(renewal_time).unwrap_or(self.suggested_window_start)
+	}
+}
+
 pub enum Order {
 	Pending(OrderPending),
 	Ready(OrderReady),
 	Valid(OrderValid),
 }
 
+impl Order {
+	/// The URL of the order, which can be used with [`Account::get_order`] to resume it later.
+	pub fn url(&self) -> &http_common::Uri {
+		match self {
+			Order::Pending(OrderPending { order_url, .. }) |
+			Order::Ready(OrderReady { order_url }) |
+			Order::Valid(OrderValid { order_url, .. }) => order_url,
+		}
+	}
+}
+
 pub struct OrderPending {
 	order_url: http_common::Uri,
 	pub authorizations: Vec<OrderPendingAuthorization>,
@@ -807,7 +1791,46 @@ pub struct OrderPending {
 pub struct OrderPendingAuthorization {
 	authorization_url: http_common::Uri,
 	challenge_url: http_common::Uri,
-	pub dns_txt_record_content: String,
+	challenge_triggered: bool,
+	pub identifier: Identifier,
+	pub challenge: PendingChallenge,
+}
+
+pub enum PendingChallenge {
+	/// The `_acme-challenge` TXT record of the identifier must contain `dns_txt_record_content`.
+	Dns01 {
+		dns_txt_record_content: String,
+	},
+
+	/// The `{dns_txt_record_label}` TXT record of the identifier must contain `dns_txt_record_content`.
+	///
+	/// `dns_txt_record_label` is specific to the account, so multiple accounts can validate the same identifier without conflicting.
+	DnsAccount01 {
+		dns_txt_record_label: String,
+		dns_txt_record_content: String,
+	},
+
+	/// The `_validation-persist` TXT record of the identifier must contain `dns_txt_record_content`.
+	///
+	/// Unlike the other challenges, the record is not specific to this order, so it can be left in place
+	/// to satisfy the challenges of future orders.
+	DnsPersist01 {
+		dns_txt_record_content: String,
+	},
+
+	/// `http://{identifier}/.well-known/acme-challenge/{token}` must respond with `key_authorization` as its body.
+	Http01 {
+		token: String,
+		key_authorization: String,
+	},
+
+	/// Port 443 of the identifier must serve `certificate_der` to TLS clients that request the `acme-tls/1` ALPN protocol.
+	///
+	/// `private_key_der` is the PKCS#8 DER of the certificate's private key. See [`TlsAlpn01Responder`].
+	TlsAlpn01 {
+		certificate_der: Vec<u8>,
+		private_key_der: Vec<u8>,
+	},
 }
 
 pub struct OrderReady {
@@ -815,17 +1838,292 @@ pub struct OrderReady {
 }
 
 pub struct OrderValid {
+	order_url: http_common::Uri,
 	certificate_url: http_common::Uri,
 }
 
+/// An error returned by the ACME server.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-6.7>
+#[derive(Debug, serde::Deserialize)]
+pub struct Problem {
+	#[serde(default, rename = "type")]
+	pub r#type: ProblemType,
+
+	pub detail: Option<String>,
+
+	pub status: Option<u16>,
+
+	pub identifier: Option<Identifier>,
+
+	#[serde(default)]
+	pub subproblems: Vec<Problem>,
+}
+
+impl std::fmt::Display for Problem {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if let Some(identifier) = &self.identifier {
+			write!(f, "{identifier}: ")?;
+		}
+
+		write!(f, "{}", self.r#type)?;
+
+		if let Some(detail) = &self.detail {
+			write!(f, ": {detail}")?;
+		}
+
+		for (i, subproblem) in self.subproblems.iter().enumerate() {
+			f.write_str(if i == 0 { " (" } else { "; " })?;
+			write!(f, "{subproblem}")?;
+		}
+		if !self.subproblems.is_empty() {
+			f.write_str(")")?;
+		}
+
+		Ok(())
+	}
+}
+
+impl std::error::Error for Problem {}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
+#[serde(from = "String")]
+pub enum ProblemType {
+	AccountDoesNotExist,
+
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc9773#section-5>
+	AlreadyReplaced,
+
+	AlreadyRevoked,
+	BadCsr,
+	BadNonce,
+	BadPublicKey,
+	BadRevocationReason,
+	BadSignatureAlgorithm,
+	Caa,
+	Compound,
+	Connection,
+	Dns,
+	ExternalAccountRequired,
+	IncorrectResponse,
+	InvalidContact,
+	Malformed,
+	OrderNotReady,
+	RateLimited,
+	RejectedIdentifier,
+	ServerInternal,
+	Tls,
+	Unauthorized,
+	UnsupportedContact,
+	UnsupportedIdentifier,
+	UserActionRequired,
+
+	/// A type that is not defined by RFC 8555.
+	Other(String),
+}
+
+impl ProblemType {
+	const ACME_URN_PREFIX: &'static str = "urn:ietf:params:acme:error:";
+}
+
+impl Default for ProblemType {
+	fn default() -> Self {
+		// Ref: <https://www.rfc-editor.org/rfc/rfc7807#section-4.2>
+		ProblemType::Other("about:blank".to_owned())
+	}
+}
+
+impl From<String> for ProblemType {
+	fn from(s: String) -> Self {
+		let Some(name) = s.strip_prefix(ProblemType::ACME_URN_PREFIX) else { return ProblemType::Other(s); };
+
+		match name {
+			"accountDoesNotExist" => ProblemType::AccountDoesNotExist,
+			"alreadyReplaced" => ProblemType::AlreadyReplaced,
+			"alreadyRevoked" => ProblemType::AlreadyRevoked,
+			"badCSR" => ProblemType::BadCsr,
+			"badNonce" => ProblemType::BadNonce,
+			"badPublicKey" => ProblemType::BadPublicKey,
+			"badRevocationReason" => ProblemType::BadRevocationReason,
+			"badSignatureAlgorithm" => ProblemType::BadSignatureAlgorithm,
+			"caa" => ProblemType::Caa,
+			"compound" => ProblemType::Compound,
+			"connection" => ProblemType::Connection,
+			"dns" => ProblemType::Dns,
+			"externalAccountRequired" => ProblemType::ExternalAccountRequired,
+			"incorrectResponse" => ProblemType::IncorrectResponse,
+			"invalidContact" => ProblemType::InvalidContact,
+			"malformed" => ProblemType::Malformed,
+			"orderNotReady" => ProblemType::OrderNotReady,
+			"rateLimited" => ProblemType::RateLimited,
+			"rejectedIdentifier" => ProblemType::RejectedIdentifier,
+			"serverInternal" => ProblemType::ServerInternal,
+			"tls" => ProblemType::Tls,
+			"unauthorized" => ProblemType::Unauthorized,
+			"unsupportedContact" => ProblemType::UnsupportedContact,
+			"unsupportedIdentifier" => ProblemType::UnsupportedIdentifier,
+			"userActionRequired" => ProblemType::UserActionRequired,
+			_ => ProblemType::Other(s),
+		}
+	}
+}
+
+impl std::fmt::Display for ProblemType {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let name = match self {
+			ProblemType::AccountDoesNotExist => "accountDoesNotExist",
+			ProblemType::AlreadyReplaced => "alreadyReplaced",
+			ProblemType::AlreadyRevoked => "alreadyRevoked",
+			ProblemType::BadCsr => "badCSR",
+			ProblemType::BadNonce => "badNonce",
+			ProblemType::BadPublicKey => "badPublicKey",
+			ProblemType::BadRevocationReason => "badRevocationReason",
+			ProblemType::BadSignatureAlgorithm => "badSignatureAlgorithm",
+			ProblemType::Caa => "caa",
+			ProblemType::Compound => "compound",
+			ProblemType::Connection => "connection",
+			ProblemType::Dns => "dns",
+			ProblemType::ExternalAccountRequired => "externalAccountRequired",
+			ProblemType::IncorrectResponse => "incorrectResponse",
+			ProblemType::InvalidContact => "invalidContact",
+			ProblemType::Malformed => "malformed",
+			ProblemType::OrderNotReady => "orderNotReady",
+			ProblemType::RateLimited => "rateLimited",
+			ProblemType::RejectedIdentifier => "rejectedIdentifier",
+			ProblemType::ServerInternal => "serverInternal",
+			ProblemType::Tls => "tls",
+			ProblemType::Unauthorized => "unauthorized",
+			ProblemType::UnsupportedContact => "unsupportedContact",
+			ProblemType::UnsupportedIdentifier => "unsupportedIdentifier",
+			ProblemType::UserActionRequired => "userActionRequired",
+			ProblemType::Other(s) => return f.write_str(s),
+		};
+		write!(f, "{}{name}", ProblemType::ACME_URN_PREFIX)
+	}
+}
+
 pub const JWS_BASE64_ENGINE: base64::engine::GeneralPurpose =
 	base64::engine::GeneralPurpose::new(
 		&base64::alphabet::URL_SAFE,
 		base64::engine::general_purpose::NO_PAD,
 	);
 
+fn serialize_base64_json<T>(value: &T) -> serde_json::Result<Vec<u8>> where T: serde::Serialize {
+	let mut writer = base64::write::EncoderWriter::new(Vec::with_capacity(1024), &JWS_BASE64_ENGINE);
+	let mut serializer = serde_json::Serializer::new(&mut writer);
+	serde::Serialize::serialize(value, &mut serializer)?;
+	Ok(writer.finish().expect("cannot fail to write to Vec<u8>"))
+}
+
+/// Returns whether the issuer CN of the last certificate in the given PEM chain is `issuer_common_name`.
+fn top_certificate_issuer_matches(certificate_chain: &str, issuer_common_name: &str) -> anyhow::Result<bool> {
+	let top_certificate =
+		x509_parser::pem::Pem::iter_from_buffer(certificate_chain.as_bytes())
+		.last()
+		.context("certificate chain is empty")?
+		.context("could not parse certificate chain PEM")?;
+	let top_certificate = top_certificate.parse_x509().context("could not parse certificate")?;
+	let matches =
+		top_certificate.issuer().iter_common_name()
+		.any(|common_name| common_name.as_str().is_ok_and(|common_name| common_name == issuer_common_name));
+	Ok(matches)
+}
+
+/// The base64url-encoded SHA-256 thumbprint of the JWK.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc7638>
+pub fn jwk_thumbprint(jwk: &Jwk<'_>) -> String {
+	let mut hasher: sha2::Sha256 = sha2::Digest::new();
+	let mut serializer = serde_json::Serializer::new(&mut hasher);
+	serde::Serialize::serialize(jwk, &mut serializer).expect("cannot fail to serialize JWK");
+	let jwk_thumbprint = sha2::Digest::finalize(hasher);
+	base64::Engine::encode(&JWS_BASE64_ENGINE, jwk_thumbprint)
+}
+
+/// The key authorization of a challenge with the given token, for the account with the given key.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-8.1>
+pub fn key_authorization(token: &str, jwk: &Jwk<'_>) -> String {
+	let jwk_thumbprint = jwk_thumbprint(jwk);
+
+	let mut key_authorization = String::with_capacity(token.len() + 1 + jwk_thumbprint.len());
+	key_authorization.push_str(token);
+	key_authorization.push('.');
+	key_authorization.push_str(&jwk_thumbprint);
+	key_authorization
+}
+
+/// The content of the `_acme-challenge` TXT record for a dns-01 challenge.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-8.4>
+pub fn dns_01_txt_record_content(key_authorization: &str) -> String {
+	let hash = <sha2::Sha256 as sha2::Digest>::digest(key_authorization);
+	base64::Engine::encode(&JWS_BASE64_ENGINE, hash)
+}
+
+/// The label of the TXT record for a dns-account-01 challenge, like `_ujmmovf2vn55tgye._acme-challenge`.
+/// The record is at this label under the identifier.
+///
+/// Ref: <https://datatracker.ietf.org/doc/draft-ietf-acme-dns-account-label/>
+pub fn dns_account_01_txt_record_label(account_url: &str) -> String {
+	const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+	let hash = <sha2::Sha256 as sha2::Digest>::digest(account_url);
+
+	// base32 of the first 10 bytes of the hash, which is exactly 16 characters with no padding.
+	let mut label = String::with_capacity(1 + 16 + "._acme-challenge".len());
+	label.push('_');
+	for chunk in hash[..10].chunks(5) {
+		let chunk = chunk.iter().fold(0_u64, |acc, &b| (acc << 8) | u64::from(b));
+		for i in (0..8).rev() {
+			label.push(char::from(BASE32_ALPHABET[usize::try_from((chunk >> (i * 5)) & 0x1f).expect("5-bit value fits in usize")]));
+		}
+	}
+	label.push_str("._acme-challenge");
+	label
+}
+
+/// The content of the `_validation-persist` TXT record for a dns-persist-01 challenge.
+///
+/// `issuer_domain_name` is one of the issuer domain names of the challenge. If `wildcard` is set, the record also authorizes
+/// wildcard certificates for the identifier.
+///
+/// Ref: <https://datatracker.ietf.org/doc/draft-ietf-acme-dns-persist/>
+pub fn dns_persist_01_txt_record_content(issuer_domain_name: &str, account_url: &str, wildcard: bool) -> String {
+	let mut content = format!("{issuer_domain_name}; accounturi={account_url}");
+	if wildcard {
+		content.push_str("; policy=wildcard");
+	}
+	content
+}
+
+/// The body of the response to the validation request of an http-01 challenge, which is the key authorization itself.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-8.3>
+pub fn http_01_response_body(key_authorization: &str) -> &str {
+	key_authorization
+}
+
+/// The value of the acmeIdentifier extension of the certificate for a tls-alpn-01 challenge.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8737#section-3>
+pub fn tls_alpn_01_extension_value(key_authorization: &str) -> [u8; 32] {
+	tls_alpn_01::extension_value(key_authorization)
+}
+
+fn check_profile(profiles: &std::collections::BTreeMap<String, String>, profile: &str) -> anyhow::Result<()> {
+	if !profiles.contains_key(profile) {
+		return Err(anyhow::anyhow!(
+			"ACME server does not offer profile {profile:?}; offered profiles are {:?}",
+			profiles.keys().collect::<Vec<_>>(),
+		));
+	}
+
+	Ok(())
+}
+
 struct ResponseWithNewNonce<TResponse> {
-	body: TResponse,
+	body: Result<TResponse, Problem>,
 	new_nonce: Option<http_common::HeaderValue>,
 }
 
@@ -838,18 +2136,48 @@ impl<TResponse> http_common::FromResponse for ResponseWithNewNonce<TResponse> wh
 		#[allow(clippy::declare_interior_mutable_const)] // Clippy doesn't like const http_common::HeaderName
 		const REPLAY_NONCE: http_common::HeaderName = http_common::HeaderName::from_static("replay-nonce");
 
+		const APPLICATION_PROBLEM_JSON: &str = "application/problem+json";
+
 		#[allow(clippy::borrow_interior_mutable_const)] // Clippy doesn't like const http_common::HeaderName
 		let new_nonce = headers.remove(&REPLAY_NONCE);
+
+		let mut body = body;
+
+		if status.is_client_error() || status.is_server_error() {
+			if let Some(body) = &mut body {
+				let is_problem =
+					body.content_type().to_str()
+					.is_ok_and(|content_type| content_type.starts_with(APPLICATION_PROBLEM_JSON));
+				if is_problem {
+					let problem = body.as_json_with_content_type(APPLICATION_PROBLEM_JSON)?;
+					return Ok(Some(ResponseWithNewNonce { body: Err(problem), new_nonce }));
+				}
+			}
+		}
+
 		match TResponse::from_response(status, body, headers) {
-			Ok(Some(body)) => Ok(Some(ResponseWithNewNonce { body, new_nonce })),
+			Ok(Some(body)) => Ok(Some(ResponseWithNewNonce { body: Ok(body), new_nonce })),
 			Ok(None) => Ok(None),
 			Err(err) => Err(err),
 		}
 	}
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct OrderObjPending {
+	#[serde(rename = "authorizations")]
+	authorization_urls: Vec<http_common::DeserializableUri>,
+}
+
+#[derive(Debug)]
+struct OrderResponse<TPending, TReady> {
+	status: OrderStatus<TPending, TReady>,
+	not_before: Option<time::OffsetDateTime>,
+	not_after: Option<time::OffsetDateTime>,
+}
+
 #[derive(Debug)]
-enum OrderResponse<TPending, TReady> {
+enum OrderStatus<TPending, TReady> {
 	Pending(TPending),
 
 	Processing {
@@ -861,6 +2189,10 @@ enum OrderResponse<TPending, TReady> {
 	Valid {
 		certificate_url: http_common::Uri,
 	},
+
+	Invalid {
+		error: Option<Problem>,
+	},
 }
 
 impl<TPending, TReady> http_common::FromResponse for OrderResponse<TPending, TReady>
@@ -873,9 +2205,21 @@ where
 		body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
 		headers: http_common::HeaderMap,
 	) -> anyhow::Result<Option<Self>> {
+		#[derive(serde::Deserialize)]
+		struct Order<TPending, TReady> {
+			#[serde(flatten)]
+			status: Status<TPending, TReady>,
+
+			#[serde(default, rename = "notBefore", deserialize_with = "time::serde::rfc3339::option::deserialize")]
+			not_before: Option<time::OffsetDateTime>,
+
+			#[serde(default, rename = "notAfter", deserialize_with = "time::serde::rfc3339::option::deserialize")]
+			not_after: Option<time::OffsetDateTime>,
+		}
+
 		#[derive(serde::Deserialize)]
 		#[serde(tag = "status")]
-		enum Order<TPending, TReady> {
+		enum Status<TPending, TReady> {
 			#[serde(rename = "pending")]
 			Pending(TPending),
 
@@ -890,21 +2234,34 @@ where
 				#[serde(rename = "certificate")]
 				certificate_url: http_common::DeserializableUri,
 			},
+
+			#[serde(rename = "invalid")]
+			Invalid {
+				error: Option<Problem>,
+			},
 		}
 
 		Ok(match (status, body) {
-			(http_common::StatusCode::CREATED | http_common::StatusCode::OK, Some(body)) => Some(match body.as_json()? {
-				Order::Pending(pending) => OrderResponse::Pending(pending),
+			(http_common::StatusCode::CREATED | http_common::StatusCode::OK, Some(body)) => {
+				let Order { status, not_before, not_after } = body.as_json()?;
 
-				Order::Processing => {
-					let retry_after = http_common::get_retry_after(&headers, std::time::Duration::from_secs(1), std::time::Duration::from_secs(30))?;
-					OrderResponse::Processing { retry_after }
-				},
+				let status = match status {
+					Status::Pending(pending) => OrderStatus::Pending(pending),
 
-				Order::Ready(ready) => OrderResponse::Ready(ready),
+					Status::Processing => {
+						let retry_after = http_common::get_retry_after(&headers, std::time::Duration::from_secs(1), std::time::Duration::from_secs(30))?;
+						OrderStatus::Processing { retry_after }
+					},
 
-				Order::Valid { certificate_url: http_common::DeserializableUri(certificate_url) } => OrderResponse::Valid { certificate_url },
-			}),
+					Status::Ready(ready) => OrderStatus::Ready(ready),
+
+					Status::Valid { certificate_url: http_common::DeserializableUri(certificate_url) } => OrderStatus::Valid { certificate_url },
+
+					Status::Invalid { error } => OrderStatus::Invalid { error },
+				};
+
+				Some(OrderResponse { status, not_before, not_after })
+			},
 
 			_ => None,
 		})
@@ -919,6 +2276,111 @@ enum Authorization<TPending> {
 
 	#[serde(rename = "valid")]
 	Valid,
+
+	#[serde(rename = "invalid")]
+	Invalid(AuthorizationFailed),
+
+	#[serde(rename = "deactivated")]
+	Deactivated(AuthorizationFailed),
+
+	#[serde(rename = "expired")]
+	Expired(AuthorizationFailed),
+
+	#[serde(rename = "revoked")]
+	Revoked(AuthorizationFailed),
+}
+
+#[derive(Debug)]
+enum AuthorizationWithChallengesResponse {
+	Pending { identifier: Identifier, wildcard: bool, challenges: Vec<ChallengePending> },
+	Valid,
+	Failed(StatusError),
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChallengePending {
+	/// Not all challenge types have a token, like dns-persist-01.
+	token: Option<String>,
+
+	r#type: String,
+
+	url: http_common::DeserializableUri,
+
+	/// Only used by dns-persist-01.
+	#[serde(default, rename = "issuer-domain-names")]
+	issuer_domain_names: Vec<String>,
+
+	/// Whether the challenge is already being processed, such as because an earlier process triggered it before it died.
+	#[serde(skip)]
+	triggered: bool,
+}
+
+impl http_common::FromResponse for AuthorizationWithChallengesResponse {
+	fn from_response(
+		status: http_common::StatusCode,
+		body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+		_headers: http_common::HeaderMap,
+	) -> anyhow::Result<Option<Self>> {
+		#[derive(serde::Deserialize)]
+		struct AuthorizationPending {
+			identifier: Identifier,
+			challenges: Vec<Challenge<ChallengePending>>,
+			#[serde(default)]
+			wildcard: bool,
+		}
+
+		Ok(match (status, body) {
+			// newAuthz responds with 201, or with 200 if it returns an existing authorization.
+			(http_common::StatusCode::CREATED | http_common::StatusCode::OK, Some(body)) => Some(match body.as_json()? {
+				Authorization::Pending(AuthorizationPending { identifier, challenges, wildcard }) => {
+					let challenges =
+						challenges.into_iter()
+						.filter_map(|challenge| match challenge {
+							Challenge::Pending(challenge) => Some(challenge),
+							Challenge::Processing(challenge) => Some(ChallengePending { triggered: true, ..challenge }),
+							Challenge::Valid |
+							Challenge::Invalid { .. } => None,
+						})
+						.collect();
+					AuthorizationWithChallengesResponse::Pending { identifier, wildcard, challenges }
+				},
+
+				Authorization::Valid => AuthorizationWithChallengesResponse::Valid,
+
+				Authorization::Invalid(failed) => AuthorizationWithChallengesResponse::Failed(failed.into_status_error("invalid")),
+				Authorization::Deactivated(failed) => AuthorizationWithChallengesResponse::Failed(failed.into_status_error("deactivated")),
+				Authorization::Expired(failed) => AuthorizationWithChallengesResponse::Failed(failed.into_status_error("expired")),
+				Authorization::Revoked(failed) => AuthorizationWithChallengesResponse::Failed(failed.into_status_error("revoked")),
+			}),
+
+			_ => None,
+		})
+	}
+}
+
+#[derive(serde::Deserialize)]
+struct AuthorizationFailed {
+	identifier: Identifier,
+
+	#[serde(default)]
+	challenges: Vec<ChallengeFailed>,
+}
+
+#[derive(serde::Deserialize)]
+struct ChallengeFailed {
+	error: Option<Problem>,
+}
+
+impl AuthorizationFailed {
+	fn into_status_error(self, status: &'static str) -> StatusError {
+		StatusError {
+			object_type: "authorization",
+			identifier: Some(self.identifier),
+			status,
+			// The authorization object itself has no error. The error is on whichever challenge failed.
+			problem: self.challenges.into_iter().find_map(|challenge| challenge.error),
+		}
+	}
 }
 
 #[derive(serde::Deserialize)]
@@ -928,8 +2390,121 @@ enum Challenge<TPending> {
 	Pending(TPending),
 
 	#[serde(rename = "processing")]
-	Processing,
+	Processing(TPending),
 
 	#[serde(rename = "valid")]
 	Valid,
+
+	#[serde(rename = "invalid")]
+	Invalid {
+		error: Option<Problem>,
+	},
+}
+
+/// An ACME object that has reached a status from which it cannot become valid.
+///
+/// Errors returned by [`Account`] can be downcast to this to distinguish a failed validation from other errors like timeouts.
+#[derive(Debug)]
+pub struct StatusError {
+	object_type: &'static str,
+	identifier: Option<Identifier>,
+	status: &'static str,
+	problem: Option<Problem>,
+}
+
+impl StatusError {
+	fn order(problem: Option<Problem>) -> Self {
+		StatusError {
+			object_type: "order",
+			identifier: None,
+			status: "invalid",
+			problem,
+		}
+	}
+}
+
+impl std::fmt::Display for StatusError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.object_type)?;
+		if let Some(identifier) = &self.identifier {
+			write!(f, " for {identifier}")?;
+		}
+		write!(f, " has {} status", self.status)?;
+		if let Some(problem) = &self.problem {
+			write!(f, ": {problem}")?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for StatusError {}
+
+#[cfg(test)]
+mod tests {
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc7638#section-3.1>
+	const RFC7638_JWK: super::Jwk<'static> = super::Jwk::Rsa {
+		e: "AQAB",
+		n: "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+	};
+
+	#[test]
+	fn jwk_serialize() {
+		assert_eq!(
+			serde_json::to_string(&super::Jwk::Ec { crv: super::EcCurve::P256, x: "x", y: "y" }).unwrap(),
+			r#"{"crv":"P-256","kty":"EC","x":"x","y":"y"}"#,
+		);
+		assert_eq!(
+			serde_json::to_string(&super::Jwk::Okp { crv: super::OkpCurve::Ed25519, x: "x" }).unwrap(),
+			r#"{"crv":"Ed25519","kty":"OKP","x":"x"}"#,
+		);
+		assert_eq!(
+			serde_json::to_string(&super::Jwk::Rsa { e: "e", n: "n" }).unwrap(),
+			r#"{"e":"e","kty":"RSA","n":"n"}"#,
+		);
+	}
+
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8037#appendix-A.3>
+	#[test]
+	fn jwk_thumbprint_okp() {
+		assert_eq!(
+			super::jwk_thumbprint(&super::Jwk::Okp { crv: super::OkpCurve::Ed25519, x: "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo" }),
+			"kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k",
+		);
+	}
+
+	#[test]
+	fn jwk_thumbprint() {
+		assert_eq!(super::jwk_thumbprint(&RFC7638_JWK), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+	}
+
+	/// Ref: <https://www.rfc-editor.org/rfc/rfc8555#section-8.1>
+	#[test]
+	fn key_authorization() {
+		let key_authorization = super::key_authorization("evaGxfADs6pSRb2LAv9IZf17Dt3juxGJ-PCt92wr-oA", &RFC7638_JWK);
+		assert_eq!(key_authorization, "evaGxfADs6pSRb2LAv9IZf17Dt3juxGJ-PCt92wr-oA.NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+
+		assert_eq!(super::dns_01_txt_record_content(&key_authorization), "ZTRx1Ckl1-tM05o5zaizTTA0yUy5AGereMgSNWC6Ll8");
+	}
+
+	/// Ref: <https://datatracker.ietf.org/doc/draft-ietf-acme-dns-account-label/>
+	#[test]
+	fn dns_account_01_txt_record_label() {
+		assert_eq!(
+			super::dns_account_01_txt_record_label("https://example.com/acme/acct/ExampleAccount"),
+			"_ujmmovf2vn55tgye._acme-challenge",
+		);
+	}
+
+	/// Ref: <https://datatracker.ietf.org/doc/draft-ietf-acme-dns-persist/>
+	#[test]
+	fn dns_persist_01_txt_record_content() {
+		assert_eq!(
+			super::dns_persist_01_txt_record_content("authority.example", "https://ca.example/acct/123", false),
+			"authority.example; accounturi=https://ca.example/acct/123",
+		);
+		assert_eq!(
+			super::dns_persist_01_txt_record_content("authority.example", "https://ca.example/acct/123", true),
+			"authority.example; accounturi=https://ca.example/acct/123; policy=wildcard",
+		);
+	}
 }
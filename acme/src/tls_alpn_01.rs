@@ -0,0 +1,130 @@
+use anyhow::Context;
+
+const ACME_TLS_1: &[u8] = b"acme-tls/1";
+
+/// Serves the tls-alpn-01 challenge certificates of an order's pending authorizations.
+pub struct TlsAlpn01Responder {
+	acceptor: tokio_rustls::TlsAcceptor,
+}
+
+impl TlsAlpn01Responder {
+	pub fn new<'a, I>(authorizations: I) -> anyhow::Result<Self>
+	where
+		I: IntoIterator<Item = &'a crate::OrderPendingAuthorization>,
+	{
+		let provider = std::sync::Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+
+		let mut certificates = std::collections::BTreeMap::new();
+
+		for authorization in authorizations {
+			let crate::PendingChallenge::TlsAlpn01 { certificate_der, private_key_der } = &authorization.challenge else { continue; };
+
+			let private_key_der =
+				tokio_rustls::rustls::pki_types::PrivateKeyDer::Pkcs8(private_key_der.clone().into());
+			let signing_key =
+				provider.key_provider.load_private_key(private_key_der)
+				.with_context(|| format!("could not load tls-alpn-01 private key for {}", authorization.identifier))?;
+			let certified_key = tokio_rustls::rustls::sign::CertifiedKey::new(
+				vec![certificate_der.clone().into()],
+				signing_key,
+			);
+			certificates.insert(server_name(&authorization.identifier), std::sync::Arc::new(certified_key));
+		}
+
+		let mut config =
+			tokio_rustls::rustls::ServerConfig::builder_with_provider(provider)
+			.with_safe_default_protocol_versions().context("could not create TLS server config")?
+			.with_no_client_auth()
+			.with_cert_resolver(std::sync::Arc::new(CertificateResolver(certificates)));
+		config.alpn_protocols = vec![ACME_TLS_1.to_owned()];
+
+		Ok(TlsAlpn01Responder {
+			acceptor: std::sync::Arc::new(config).into(),
+		})
+	}
+
+	/// Completes TLS handshakes on `listener` until the returned future is dropped.
+	///
+	/// The listener is expected to be bound to port 443 of the hosts named by the authorizations.
+	pub async fn serve(&self, listener: &tokio::net::TcpListener) -> anyhow::Result<std::convert::Infallible> {
+		const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+		let mut handshakes = futures_util::stream::FuturesUnordered::new();
+
+		loop {
+			let stream = std::pin::pin!(listener.accept());
+			let next = futures_util::future::select(stream, futures_util::StreamExt::next(&mut handshakes));
+			let (stream, _) = match next.await {
+				futures_util::future::Either::Left((stream, _)) => stream.context("could not accept connection")?,
+
+				futures_util::future::Either::Right((Some(()), _)) => continue,
+
+				// `handshakes` is empty.
+				futures_util::future::Either::Right((None, stream)) => stream.await.context("could not accept connection")?,
+			};
+
+			handshakes.push(async move {
+				// The validation server only needs the certificate, so the connection is closed as soon as the handshake completes.
+				// Failed handshakes are expected for clients that don't request the acme-tls/1 protocol.
+				_ = tokio::time::timeout(HANDSHAKE_TIMEOUT, self.acceptor.accept(stream)).await;
+			});
+		}
+	}
+}
+
+pub(crate) fn certificate(identifier: &crate::Identifier, key_authorization: &str) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+	let key_pair = rcgen::KeyPair::generate().context("could not generate tls-alpn-01 private key")?;
+
+	let mut params = rcgen::CertificateParams::default();
+	params.subject_alt_names = vec![match identifier {
+		crate::Identifier::Dns(name) => rcgen::SanType::DnsName(name.clone().try_into().context("could not create tls-alpn-01 certificate SAN")?),
+		crate::Identifier::Ip(addr) => rcgen::SanType::IpAddress(*addr),
+	}];
+	params.custom_extensions = vec![rcgen::CustomExtension::new_acme_identifier(&extension_value(key_authorization))];
+
+	let certificate = params.self_signed(&key_pair).context("could not create tls-alpn-01 certificate")?;
+
+	Ok((certificate.der().to_vec(), key_pair.serialize_der()))
+}
+
+/// The SNI that the validation server sends for the given identifier.
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc8738#section-6>
+fn server_name(identifier: &crate::Identifier) -> String {
+	match identifier {
+		crate::Identifier::Dns(name) => name.clone(),
+
+		crate::Identifier::Ip(std::net::IpAddr::V4(addr)) => {
+			let [a, b, c, d] = addr.octets();
+			format!("{d}.{c}.{b}.{a}.in-addr.arpa")
+		},
+
+		crate::Identifier::Ip(std::net::IpAddr::V6(addr)) => {
+			let mut server_name = String::with_capacity(32 * 2 + "ip6.arpa".len());
+			for b in addr.octets().into_iter().rev() {
+				_ = std::fmt::Write::write_fmt(&mut server_name, format_args!("{:x}.{:x}.", b & 0x0f, b >> 4));
+			}
+			server_name.push_str("ip6.arpa");
+			server_name
+		},
+	}
+}
+
+pub(crate) fn extension_value(key_authorization: &str) -> [u8; 32] {
+	<sha2::Sha256 as sha2::Digest>::digest(key_authorization).into()
+}
+
+#[derive(Debug)]
+struct CertificateResolver(std::collections::BTreeMap<String, std::sync::Arc<tokio_rustls::rustls::sign::CertifiedKey>>);
+
+impl tokio_rustls::rustls::server::ResolvesServerCert for CertificateResolver {
+	fn resolve(&self, client_hello: tokio_rustls::rustls::server::ClientHello<'_>) -> Option<std::sync::Arc<tokio_rustls::rustls::sign::CertifiedKey>> {
+		// RFC 8737 requires that the challenge certificate is only served to clients that request the acme-tls/1 protocol.
+		if !client_hello.alpn()?.any(|protocol| protocol == ACME_TLS_1) {
+			return None;
+		}
+
+		let server_name = client_hello.server_name()?;
+		self.0.get(server_name).cloned()
+	}
+}
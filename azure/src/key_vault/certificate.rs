@@ -1,5 +1,7 @@
+use anyhow::Context;
+
 impl super::Client<'_> {
-	pub async fn csr_create(&self, certificate_name: &str, common_name: &str, key_type: CreateCsrKeyType) -> anyhow::Result<String> {
+	pub async fn csr_create(&self, certificate_name: &str, identifiers: &[acme::Identifier], key_type: CreateCsrKeyType) -> anyhow::Result<String> {
 		#[derive(serde::Serialize)]
 		struct Request<'a> {
 			policy: RequestPolicy<'a>,
@@ -33,7 +35,10 @@ impl super::Client<'_> {
 
 		#[derive(serde::Serialize)]
 		struct RequestPolicyX509PropsSans<'a> {
+			#[serde(skip_serializing_if = "<[_]>::is_empty")]
 			dns_names: &'a [&'a str],
+			#[serde(rename = "ipAddresses", skip_serializing_if = "<[_]>::is_empty")]
+			ip_addresses: &'a [std::net::IpAddr],
 		}
 
 		#[derive(serde::Deserialize)]
@@ -54,17 +59,29 @@ impl super::Client<'_> {
 			}
 		}
 
+		let common_name = identifiers.first().context("CSR must have at least one identifier")?;
+
+		let mut dns_names = vec![];
+		let mut ip_addresses = vec![];
+		for identifier in identifiers {
+			match identifier {
+				acme::Identifier::Dns(name) => dns_names.push(&**name),
+				acme::Identifier::Ip(addr) => ip_addresses.push(*addr),
+			}
+		}
+
 		let csr =
 			self.logger.report_operation(
 				"azure/key_vault/csr",
 				(self.key_vault_name, certificate_name),
-				log2::ScopedObjectOperation::Create { value: format_args!("{:?}", (common_name, key_type)) },
+				log2::ScopedObjectOperation::Create { value: format_args!("{:?}", (identifiers, key_type)) },
 				async {
 					let Response { csr } =
 						crate::request(
 							self,
 							http_common::Method::POST,
-							format_args!("/certificates/{certificate_name}/create?api-version=7.4"),
+							// IP address SANs are only supported starting from 7.6
+							format_args!("/certificates/{certificate_name}/create?api-version=7.6"),
 							Some(&Request {
 								policy: RequestPolicy {
 									issuer: RequestPolicyIssuer {
@@ -77,10 +94,8 @@ impl super::Client<'_> {
 									},
 									x509_props: RequestPolicyX509Props {
 										sans: RequestPolicyX509PropsSans {
-											dns_names: &[
-												common_name,
-												&format!("*.{common_name}"),
-											],
+											dns_names: &dns_names,
+											ip_addresses: &ip_addresses,
 										},
 										subject: format_args!("CN={common_name}"),
 									},
@@ -139,18 +154,18 @@ impl super::Client<'_> {
 							None => id.into_owned(),
 						};
 
-						let (trailing_garbage, cer) = x509_parser::parse_x509_certificate(&cer)?;
+						let (trailing_garbage, parsed) = x509_parser::parse_x509_certificate(&cer)?;
 						if !trailing_garbage.is_empty() {
 							return Err(anyhow::anyhow!("cert has trailing garbage"));
 						}
 
 						let (not_before, not_after) = {
-							let validity = cer.validity();
+							let validity = parsed.validity();
 							(validity.not_before.to_datetime(), validity.not_after.to_datetime())
 						};
 
 						let ari_id =
-							cer
+							parsed
 							.get_extension_unique(&x509_parser::oid_registry::OID_X509_EXT_AUTHORITY_KEY_IDENTIFIER)?
 							.and_then(|extension| match extension.parsed_extension() {
 								x509_parser::extensions::ParsedExtension::AuthorityKeyIdentifier(aki) => {
@@ -158,7 +173,7 @@ impl super::Client<'_> {
 
 									ari_id.push('.');
 
-									let serial = cer.raw_serial();
+									let serial = parsed.raw_serial();
 									if serial.first().is_some_and(|b| b & 0x80 != 0) {
 										// Non-positive serial is invalid.
 										return None;
@@ -175,6 +190,7 @@ impl super::Client<'_> {
 							ari_id,
 							not_before,
 							not_after,
+							der: cer,
 						})))
 					},
 
@@ -300,10 +316,22 @@ impl serde::Serialize for CreateCsrKeyType {
 	}
 }
 
-#[derive(Debug)]
 pub struct Certificate {
 	pub version: String,
 	pub ari_id: Option<String>,
 	pub not_before: time::OffsetDateTime,
 	pub not_after: time::OffsetDateTime,
+	pub der: Vec<u8>,
+}
+
+impl std::fmt::Debug for Certificate {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		// Skip `der` since it's too large to be useful in logs.
+		f.debug_struct("Certificate")
+		.field("version", &self.version)
+		.field("ari_id", &self.ari_id)
+		.field("not_before", &self.not_before)
+		.field("not_after", &self.not_after)
+		.finish_non_exhaustive()
+	}
 }
@@ -20,7 +20,7 @@ impl super::Client<'_> {
 				(self.key_vault_name, key_name),
 				log2::ScopedObjectOperation::Create { value: format_args!("{:?}", (kty, crv)) },
 				async {
-					let CreateOrGetKeyResponse { key } =
+					let response: CreateOrGetKeyResponse =
 						crate::request(
 							self,
 							http_common::Method::POST,
@@ -31,7 +31,7 @@ impl super::Client<'_> {
 								key_ops: &["sign", "verify"],
 							}),
 						).await?;
-					Ok::<_, anyhow::Error>(key)
+					Ok::<_, anyhow::Error>(response)
 				},
 			).await?;
 
@@ -39,11 +39,35 @@ impl super::Client<'_> {
 		Ok(key)
 	}
 
+	/// Returns the newest enabled version of the key.
+	///
+	/// Unlike getting the key without a version, this skips versions that have been disabled, so disabling the newest version
+	/// makes the previous one current again.
 	pub async fn key_get<'b>(
 		&'b self,
 		key_name: &str,
 	) -> anyhow::Result<Option<Key<'b>>> {
-		struct Response(Option<CreateOrGetKeyResponse>);
+		struct Response(Option<VersionsResponse>);
+
+		#[derive(serde::Deserialize)]
+		struct VersionsResponse {
+			value: Vec<KeyVersion>,
+
+			#[serde(rename = "nextLink")]
+			next_link: Option<String>,
+		}
+
+		#[derive(serde::Deserialize)]
+		struct KeyVersion {
+			kid: String,
+			attributes: KeyVersionAttributes,
+		}
+
+		#[derive(serde::Deserialize)]
+		struct KeyVersionAttributes {
+			enabled: bool,
+			created: i64,
+		}
 
 		impl http_common::FromResponse for Response {
 			fn from_response(
@@ -60,19 +84,100 @@ impl super::Client<'_> {
 		}
 
 		let key = self.logger.report_operation("azure/key_vault/key", (self.key_vault_name, key_name), <log2::ScopedObjectOperation>::Get, async {
-			let Response(response) =
+			let mut newest_enabled_version: Option<KeyVersion> = None;
+
+			let mut next_url: Option<http_common::Uri> = None;
+			loop {
+				let Response(response) =
+					if let Some(next_url) = next_url.take() {
+						crate::request(self, http_common::Method::GET, next_url, None::<&()>).await?
+					}
+					else {
+						crate::request(self, http_common::Method::GET, format_args!("/keys/{key_name}/versions?api-version=7.4"), None::<&()>).await?
+					};
+				let Some(VersionsResponse { value, next_link }) = response else {
+					return Ok(None);
+				};
+
+				for version in value {
+					if version.attributes.enabled && newest_enabled_version.as_ref().is_none_or(|newest| version.attributes.created > newest.attributes.created) {
+						newest_enabled_version = Some(version);
+					}
+				}
+
+				let Some(next_link) = next_link else {
+					break;
+				};
+				next_url = Some(next_link.try_into().context("could not parse next link")?);
+			}
+
+			let KeyVersion { kid, .. } = newest_enabled_version.context("key has no enabled versions")?;
+			let get_url: http_common::Uri = format!("{kid}?api-version=7.4").try_into().context("could not construct get URL")?;
+			let response: CreateOrGetKeyResponse = crate::request(self, http_common::Method::GET, get_url, None::<&()>).await?;
+			Ok::<_, anyhow::Error>(Some(response))
+		}).await?;
+
+		let key = key.map(|key| Key::new(key, self)).transpose()?;
+		Ok(key)
+	}
+
+	/// Disables the specific version of the key that `key` refers to.
+	pub async fn key_disable(&self, key: &Key<'_>) -> anyhow::Result<()> {
+		#[derive(serde::Serialize)]
+		struct Request {
+			attributes: RequestAttributes,
+		}
+
+		#[derive(serde::Serialize)]
+		struct RequestAttributes {
+			enabled: bool,
+		}
+
+		let update_url: http_common::Uri = format!("{}?api-version=7.4", key.kid).try_into().context("could not construct update URL")?;
+
+		self.logger.report_operation("azure/key_vault/key", &*key.kid, log2::ScopedObjectOperation::Create { value: "disabled" }, async {
+			let _: CreateOrGetKeyResponse =
 				crate::request(
 					self,
-					http_common::Method::GET,
-					format_args!("/keys/{key_name}?api-version=7.4"),
-					None::<&()>,
+					http_common::Method::PATCH,
+					update_url,
+					Some(&Request {
+						attributes: RequestAttributes {
+							enabled: false,
+						},
+					}),
 				).await?;
-			let key = response.map(|CreateOrGetKeyResponse { key }| key);
-			Ok::<_, anyhow::Error>(key)
+			Ok::<_, anyhow::Error>(())
 		}).await?;
 
-		let key = key.map(|key| Key::new(key, self)).transpose()?;
-		Ok(key)
+		Ok(())
+	}
+
+	/// Replaces the tags of the specific version of the key that `key` refers to.
+	///
+	/// [`Key::tags`] of `key` still returns the tags it had when it was fetched.
+	pub async fn key_set_tags(&self, key: &Key<'_>, tags: &std::collections::BTreeMap<String, String>) -> anyhow::Result<()> {
+		#[derive(serde::Serialize)]
+		struct Request<'a> {
+			tags: &'a std::collections::BTreeMap<String, String>,
+		}
+
+		let update_url: http_common::Uri = format!("{}?api-version=7.4", key.kid).try_into().context("could not construct update URL")?;
+
+		self.logger.report_operation("azure/key_vault/key", &*key.kid, log2::ScopedObjectOperation::Create { value: format_args!("tags {tags:?}") }, async {
+			let _: CreateOrGetKeyResponse =
+				crate::request(
+					self,
+					http_common::Method::PATCH,
+					update_url,
+					Some(&Request {
+						tags,
+					}),
+				).await?;
+			Ok::<_, anyhow::Error>(())
+		}).await?;
+
+		Ok(())
 	}
 }
 
@@ -86,21 +191,26 @@ pub enum EcKty {
 }
 
 pub struct Key<'a> {
-	crv: acme::EcCurve,
 	kid: String,
-	x: String,
-	y: String,
+	params: KeyParams,
+	tags: std::collections::BTreeMap<String, String>,
 	client: &'a super::Client<'a>,
 	sign_url: http_common::Uri,
 }
 
 impl acme::AccountKey for Key<'_> {
 	fn as_jwk(&self) -> acme::Jwk<'_> {
-		acme::Jwk {
-			crv: self.crv,
-			kty: "EC",
-			x: &self.x,
-			y: &self.y,
+		match &self.params {
+			KeyParams::Ec { crv, x, y } => acme::Jwk::Ec {
+				crv: *crv,
+				x,
+				y,
+			},
+
+			KeyParams::Rsa { e, n } => acme::Jwk::Rsa {
+				e,
+				n,
+			},
 		}
 	}
 
@@ -150,7 +260,7 @@ impl acme::AccountKey for Key<'_> {
 				}
 			}
 
-			let alg = key.crv.jws_sign_alg();
+			let alg = acme::AccountKey::as_jwk(key).jws_sign_alg();
 
 			let signature = key.client.logger.report_operation("azure/key_vault/key/signature", &key.kid, log2::ScopedObjectOperation::Create { value: "" }, async move {
 				let KeyVaultSignResponse { value: signature } =
@@ -168,31 +278,47 @@ impl acme::AccountKey for Key<'_> {
 			Ok(signature)
 		}
 
-		let digest = hash!(self.crv, digest, {
-			acme::EcCurve::P256 => sha2::Sha256,
-			acme::EcCurve::P384 => sha2::Sha384,
-			acme::EcCurve::P521 => sha2::Sha512,
+		let digest = hash!(&self.params, digest, {
+			KeyParams::Ec { crv: acme::EcCurve::P256, .. } | KeyParams::Rsa { .. } => sha2::Sha256,
+			KeyParams::Ec { crv: acme::EcCurve::P384, .. } => sha2::Sha384,
+			KeyParams::Ec { crv: acme::EcCurve::P521, .. } => sha2::Sha512,
 		});
 
 		Box::pin(sign_inner(self, digest))
 	}
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Debug, serde::Deserialize)]
 struct CreateOrGetKeyResponse {
 	key: KeyResponse,
+
+	#[serde(default)]
+	tags: std::collections::BTreeMap<String, String>,
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct KeyResponse {
-	crv: acme::EcCurve,
 	kid: String,
-	// rustc thinks this field is unused, but it's being used to assert the key is one of the EC types,
-	// or else the deserialization wouldn't succeed.
-	#[allow(unused)]
-	kty: EcKty,
-	x: String,
-	y: String,
+	#[serde(flatten)]
+	params: KeyParams,
+}
+
+/// RSA keys are not created by this crate, but are supported so that existing RSA account keys can be used.
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "kty")]
+enum KeyParams {
+	#[serde(rename = "EC", alias = "EC-HSM")]
+	Ec {
+		crv: acme::EcCurve,
+		x: String,
+		y: String,
+	},
+
+	#[serde(rename = "RSA", alias = "RSA-HSM")]
+	Rsa {
+		e: String,
+		n: String,
+	},
 }
 
 impl http_common::FromResponse for CreateOrGetKeyResponse {
@@ -209,14 +335,23 @@ impl http_common::FromResponse for CreateOrGetKeyResponse {
 }
 
 impl<'a> Key<'a> {
-	fn new(key: KeyResponse, client: &'a super::Client<'a>) -> anyhow::Result<Self> {
+	/// The identifier of the specific version of the key.
+	pub fn kid(&self) -> &str {
+		&self.kid
+	}
+
+	/// The tags of the specific version of the key, as of when it was fetched.
+	pub fn tags(&self) -> &std::collections::BTreeMap<String, String> {
+		&self.tags
+	}
+
+	fn new(CreateOrGetKeyResponse { key, tags }: CreateOrGetKeyResponse, client: &'a super::Client<'a>) -> anyhow::Result<Self> {
 		let sign_url = format!("{}/sign?api-version=7.4", key.kid).try_into().context("could not construct sign URL")?;
 
 		Ok(Key {
-			crv: key.crv,
 			kid: key.kid,
-			x: key.x,
-			y: key.y,
+			params: key.params,
+			tags,
 			client,
 			sign_url,
 		})
@@ -4,6 +4,8 @@ pub use certificate::{Certificate, CreateCsrKeyType};
 mod key;
 pub use key::{EcKty, Key};
 
+mod secret;
+
 use anyhow::Context;
 
 pub struct Client<'a> {
@@ -0,0 +1,84 @@
+impl super::Client<'_> {
+	pub async fn secret_get(&self, secret_name: &str) -> anyhow::Result<Option<log2::Secret<String>>> {
+		struct Response(Option<log2::Secret<String>>);
+
+		impl http_common::FromResponse for Response {
+			fn from_response(
+				status: http_common::StatusCode,
+				body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+				_headers: http_common::HeaderMap,
+			) -> anyhow::Result<Option<Self>> {
+				#[derive(serde::Deserialize)]
+				struct ResponseInner {
+					value: String,
+				}
+
+				Ok(match (status, body) {
+					(http_common::StatusCode::OK, Some(body)) => {
+						let ResponseInner { value } = body.as_json()?;
+						Some(Response(Some(log2::Secret(value))))
+					},
+
+					(http_common::StatusCode::NOT_FOUND, _) => Some(Response(None)),
+
+					_ => None,
+				})
+			}
+		}
+
+		let secret = self.logger.report_operation("azure/key_vault/secret", (self.key_vault_name, secret_name), <log2::ScopedObjectOperation>::Get, async {
+			let Response(secret) =
+				crate::request(
+					self,
+					http_common::Method::GET,
+					format_args!("/secrets/{secret_name}?api-version=7.4"),
+					None::<&()>,
+				).await?;
+			Ok::<_, anyhow::Error>(secret)
+		}).await?;
+
+		Ok(secret)
+	}
+
+	pub async fn secret_set(&self, secret_name: &str, value: &str) -> anyhow::Result<()> {
+		#[derive(serde::Serialize)]
+		struct Request<'a> {
+			value: &'a str,
+		}
+
+		struct Response;
+
+		impl http_common::FromResponse for Response {
+			fn from_response(
+				status: http_common::StatusCode,
+				_body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+				_headers: http_common::HeaderMap,
+			) -> anyhow::Result<Option<Self>> {
+				Ok(match status {
+					http_common::StatusCode::OK => Some(Response),
+					_ => None,
+				})
+			}
+		}
+
+		self.logger.report_operation(
+			"azure/key_vault/secret",
+			(self.key_vault_name, secret_name),
+			log2::ScopedObjectOperation::Create { value: format_args!("{:?}", log2::Secret(value)) },
+			async {
+				let _: Response =
+					crate::request(
+						self,
+						http_common::Method::PUT,
+						format_args!("/secrets/{secret_name}?api-version=7.4"),
+						Some(&Request {
+							value,
+						}),
+					).await?;
+				Ok::<_, anyhow::Error>(())
+			},
+		).await?;
+
+		Ok(())
+	}
+}
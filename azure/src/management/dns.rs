@@ -44,7 +44,74 @@ impl super::Client<'_> {
 		Ok(name_servers)
 	}
 
+	/// Returns the TXT record set, or `None` if the record set does not exist.
+	pub async fn dns_txt_record_get(&self, dns_zone_name: &str, name: &str) -> anyhow::Result<Option<DnsTxtRecordSet>> {
+		struct Response(Option<DnsTxtRecordSet>);
+
+		impl http_common::FromResponse for Response {
+			fn from_response(
+				status: http_common::StatusCode,
+				body: Option<&mut http_common::ResponseBody<impl std::io::Read>>,
+				_headers: http_common::HeaderMap,
+			) -> anyhow::Result<Option<Self>> {
+				#[derive(serde::Deserialize)]
+				struct ResponseInner {
+					properties: ResponseProperties,
+				}
+
+				#[derive(serde::Deserialize)]
+				struct ResponseProperties {
+					#[serde(rename = "TTL")]
+					ttl: u64,
+
+					#[serde(rename = "TXTRecords", default)]
+					txt_records: Vec<ResponsePropertiesTxtRecord>,
+				}
+
+				#[derive(serde::Deserialize)]
+				struct ResponsePropertiesTxtRecord {
+					value: Vec<String>,
+				}
+
+				Ok(match (status, body) {
+					(http_common::StatusCode::OK, Some(body)) => {
+						let ResponseInner { properties: ResponseProperties { ttl, txt_records } } = body.as_json()?;
+						// Long TXT record values are split into multiple strings of at most 255 characters.
+						let values = txt_records.into_iter().map(|ResponsePropertiesTxtRecord { value }| value.concat()).collect();
+						Some(Response(Some(DnsTxtRecordSet { ttl, values })))
+					},
+
+					(http_common::StatusCode::NOT_FOUND, _) => Some(Response(None)),
+
+					_ => None,
+				})
+			}
+		}
+
+		let txt_records = self.logger.report_operation("azure/dns/txtrecord", (dns_zone_name, name), <log2::ScopedObjectOperation>::Get, async {
+			let Response(txt_records) =
+				crate::request(
+					self,
+					http_common::Method::GET,
+					format_args!("/providers/Microsoft.Network/dnsZones/{dns_zone_name}/TXT/{name}?api-version=2018-05-01"),
+					None::<&()>,
+				).await?;
+			Ok::<_, anyhow::Error>(txt_records)
+		}).await?;
+
+		Ok(txt_records)
+	}
+
+	/// Creates or replaces the TXT record set with a TTL of one second, for short-lived records like the dns-01 one.
 	pub async fn dns_txt_record_create<'b, I>(&self, dns_zone_name: &str, name: &str, content: I) -> anyhow::Result<()>
+	where
+		I: IntoIterator<Item = &'b str>,
+		I::IntoIter: Clone,
+	{
+		self.dns_txt_record_set(dns_zone_name, name, 1, content).await
+	}
+
+	async fn dns_txt_record_set<'b, I>(&self, dns_zone_name: &str, name: &str, ttl: u64, content: I) -> anyhow::Result<()>
 	where
 		I: IntoIterator<Item = &'b str>,
 		I::IntoIter: Clone,
@@ -102,7 +169,7 @@ impl super::Client<'_> {
 					format_args!("/providers/Microsoft.Network/dnsZones/{dns_zone_name}/TXT/{name}?api-version=2018-05-01"),
 					Some(&Request {
 						properties: RequestProperties {
-							ttl: 1,
+							ttl,
 							txt_records: RequestPropertiesTxtRecords(content.into_iter().map(|content| RequestPropertiesTxtRecord { value: [content] })),
 						},
 					}),
@@ -114,6 +181,29 @@ impl super::Client<'_> {
 		Ok(())
 	}
 
+	/// Adds `contents` to the TXT record set, keeping its existing records and TTL, unless it already contains them.
+	///
+	/// Returns whether the record set was changed. This is meant for long-lived records like the dns-persist-01 one,
+	/// which only need to be written the first time. A new record set is created with a TTL of one hour.
+	pub async fn dns_txt_record_add(&self, dns_zone_name: &str, name: &str, contents: &[String]) -> anyhow::Result<bool> {
+		let DnsTxtRecordSet { ttl, mut values } =
+			self.dns_txt_record_get(dns_zone_name, name).await?
+			.unwrap_or(DnsTxtRecordSet { ttl: 3600, values: vec![] });
+
+		let original_len = values.len();
+		for content in contents {
+			if !values.contains(content) {
+				values.push(content.clone());
+			}
+		}
+		if values.len() == original_len {
+			return Ok(false);
+		}
+
+		self.dns_txt_record_set(dns_zone_name, name, ttl, values.iter().map(|content| &**content)).await?;
+		Ok(true)
+	}
+
 	pub async fn dns_txt_record_delete(&self, dns_zone_name: &str, name: &str) -> anyhow::Result<()> {
 		struct Response;
 
@@ -146,3 +236,11 @@ impl super::Client<'_> {
 		Ok(())
 	}
 }
+
+#[derive(Debug)]
+pub struct DnsTxtRecordSet {
+	pub ttl: u64,
+
+	/// The values of the TXT records in the record set.
+	pub values: Vec<String>,
+}
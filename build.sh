@@ -44,6 +44,7 @@ secret_settings="$(
         --arg TOP_LEVEL_DOMAIN_NAME "$TOP_LEVEL_DOMAIN_NAME" \
         '{
             "acme_contact_url": $ACME_CONTACT_URL,
+            "azure_dns_zone_names": [$TOP_LEVEL_DOMAIN_NAME],
             "azure_key_vault_acme_account_key_name": $AZURE_KEY_VAULT_ACME_ACCOUNT_KEY_NAME,
             "azure_key_vault_acme_account_key_type": "ec:p384",
             "azure_key_vault_certificate_key_type": "rsa:4096:exportable",
@@ -53,14 +54,26 @@ secret_settings="$(
             "azure_log_analytics_workspace_resource_group_name": $AZURE_LOG_ANALYTICS_WORKSPACE_RESOURCE_GROUP_NAME,
             "azure_resource_group_name": $AZURE_RESOURCE_GROUP_NAME,
             "azure_subscription_id": $AZURE_SUBSCRIPTION_ID,
-            "top_level_domain_name": $TOP_LEVEL_DOMAIN_NAME
+            "domain_names": [$TOP_LEVEL_DOMAIN_NAME, "*.\($TOP_LEVEL_DOMAIN_NAME)"]
         }'
 )"
 
 func_name='renew-cert'
 
 rm -rf ./dist
-mkdir -p "./dist/$func_name"
+mkdir -p "./dist/$func_name" ./dist/revoke-cert ./dist/rollover-account-key
+
+for manual_func_name in 'revoke-cert' 'rollover-account-key'; do
+    >"./dist/$manual_func_name/function.json" $JQ --null-input \
+        '{
+            "bindings": [{
+                "name": "main",
+                "type": "httpTrigger",
+                "methods": ["Post"],
+                "authLevel": "function",
+            }]
+        }'
+done
 
 case "$target" in
     'debug')
@@ -6,9 +6,7 @@ pub async fn main(
 	settings: &Settings<'_>,
 	logger: &log2::Logger,
 ) -> anyhow::Result<()> {
-	let user_agent: http_common::HeaderValue =
-		concat!("github.com/Arnavion/acme-azure-function ", env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"))
-		.parse().expect("hard-coded user agent is valid HeaderValue");
+	let user_agent = user_agent();
 
 	let azure_key_vault_client = azure::key_vault::Client::new(
 		&settings.azure_key_vault_name,
@@ -23,20 +21,101 @@ pub async fn main(
 		logger,
 	).await.context("could not initialize ACME API client")?;
 
-	{
+	// Validate this before checking whether the certificate needs to be renewed, so that a misconfigured profile is caught
+	// as soon as possible instead of only when the certificate is due for renewal.
+	if let Some(profile) = &settings.acme_profile {
+		acme_client.check_profile(profile)?;
+	}
+
+	let account_key = {
+		let account_key = azure_key_vault_client.key_get(&settings.azure_key_vault_acme_account_key_name).await?;
+		if let Some(account_key) = account_key {
+			account_key
+		}
+		else {
+			let (kty, crv) = settings.azure_key_vault_acme_account_key_type;
+			azure_key_vault_client.key_create(
+				&settings.azure_key_vault_acme_account_key_name,
+				kty,
+				crv,
+			).await?
+		}
+	};
+
+	let mut account_key_tags = account_key.tags().clone();
+
+	// The URL of the terms of service that the ACME account agreed to is recorded in a tag of the ACME account key.
+	// If there is no such tag, the account is new or predates the tag, and agrees to the current terms of service below.
+	if let Some(terms_of_service_url) = &acme_client.meta().terms_of_service {
+		let record_terms_of_service_url = match account_key_tags.get(ACME_TERMS_OF_SERVICE_URL_TAG_NAME) {
+			None => true,
+
+			Some(agreed_terms_of_service_url) if agreed_terms_of_service_url == terms_of_service_url => false,
+
+			Some(agreed_terms_of_service_url) => {
+				let acknowledged = settings.acme_terms_of_service_url.as_deref() == Some(&**terms_of_service_url);
+				if !acknowledged {
+					logger.report_warning(format_args!(
+						"ACME server's terms of service changed from {agreed_terms_of_service_url:?} to {terms_of_service_url:?}; \
+						set acme_terms_of_service_url to the new URL after reviewing them",
+					));
+
+					if settings.acme_terms_of_service_acknowledgement_required.unwrap_or(false) {
+						return Err(anyhow::anyhow!(
+							"ACME server's terms of service {terms_of_service_url:?} have not been acknowledged",
+						));
+					}
+				}
+
+				acknowledged
+			},
+		};
+
+		if record_terms_of_service_url {
+			account_key_tags.insert(ACME_TERMS_OF_SERVICE_URL_TAG_NAME.to_owned(), terms_of_service_url.clone());
+			azure_key_vault_client.key_set_tags(&account_key, &account_key_tags).await?;
+		}
+	}
+
+	// The ARI ID of the certificate being replaced, if the ACME server supports ARI.
+	let replaces = {
 		let now = time::OffsetDateTime::now_utc();
 
 		let certificate = azure_key_vault_client.certificate_get(&settings.azure_key_vault_certificate_name).await?;
 		if let Some(certificate) = certificate {
-			let renewal_suggested_window_start =
-				if let Some(ari_id) = certificate.ari_id {
-					acme_client.renewal_suggested_window_start(&ari_id).await?
+			let renewal_info =
+				if let Some(ari_id) = &certificate.ari_id {
+					// Fall back to the not-after-based calculation if ARI fails.
+					match acme_client.renewal_info(ari_id).await {
+						Ok(renewal_info) => renewal_info,
+						Err(err) => {
+							logger.report_error(&err);
+							None
+						},
+					}
 				}
 				else {
 					None
 				};
+			if let Some(acme::RenewalInfo { suggested_window_start, suggested_window_end, explanation_url, retry_after }) = &renewal_info {
+				logger.report_state(
+					"azure/key_vault/certificate",
+					(&settings.azure_key_vault_name, &settings.azure_key_vault_certificate_name),
+					format_args!("suggested renewal window is {suggested_window_start:?} to {suggested_window_end:?}, retry after {retry_after:?}"),
+				);
+
+				// The ACME server only provides an explanation when it has moved the window, such as because the certificate will be revoked.
+				if let Some(explanation_url) = explanation_url {
+					logger.report_warning(format_args!(
+						"ACME server moved the suggested renewal window of certificate {:?}: {explanation_url}",
+						settings.azure_key_vault_certificate_name,
+					));
+				}
+			}
 			let renew_after =
-				renewal_suggested_window_start.or_else(|| {
+				certificate.ari_id.as_deref().zip(renewal_info.as_ref())
+				.map(|(ari_id, renewal_info)| renewal_info.renewal_time(ari_id))
+				.or_else(|| {
 					// Renew if less than 1/3rd of the validity is left to match Let's Encrypt's recommendation.
 					//
 					//        (not_after - now) <= (not_after - not_before) / 3
@@ -62,34 +141,152 @@ pub async fn main(
 				logger.report_state(
 					"azure/key_vault/certificate",
 					(&settings.azure_key_vault_name, &settings.azure_key_vault_certificate_name),
-					"does not need to be renewed until {renew_after:?}",
+					format_args!("does not need to be renewed until {renew_after:?}"),
 				);
 				return Ok(());
 			}
-		}
-	}
 
-	let account_key = {
-		let account_key = azure_key_vault_client.key_get(&settings.azure_key_vault_acme_account_key_name).await?;
-		if let Some(account_key) = account_key {
-			account_key
+			certificate.ari_id.filter(|_| renewal_info.is_some())
 		}
 		else {
-			let (kty, crv) = settings.azure_key_vault_acme_account_key_type;
-			azure_key_vault_client.key_create(
-				&settings.azure_key_vault_acme_account_key_name,
-				kty,
-				crv,
-			).await?
+			None
 		}
 	};
 
+	let eab_hmac_key =
+		if let Some(secret_name) = &settings.azure_key_vault_acme_eab_hmac_key_secret_name {
+			let log2::Secret(eab_hmac_key) =
+				azure_key_vault_client.secret_get(secret_name).await?
+				.context("EAB HMAC key secret does not exist")?;
+			Some(eab_hmac_key)
+		}
+		else {
+			None
+		};
+	let external_account_binding = match (&settings.acme_eab_key_id, &eab_hmac_key) {
+		(Some(key_id), Some(hmac_key)) => Some(acme::ExternalAccountBinding { key_id, hmac_key }),
+		(None, None) => None,
+		_ => return Err(anyhow::anyhow!("acme_eab_key_id and azure_key_vault_acme_eab_hmac_key_secret_name must either both be set or both be unset")),
+	};
+
 	let mut acme_account = acme_client.new_account(
 		&settings.acme_contact_url,
 		&account_key,
+		external_account_binding,
 	).await.context("could not initialize ACME API client")?;
 
-	let mut acme_order = acme_account.place_order(&settings.top_level_domain_name).await?;
+	// Some ACME servers don't return the contact URLs of the account, so the contact URL that was last sent
+	// is recorded in a tag of the ACME account key instead of comparing against the server's response.
+	if account_key_tags.get(ACME_CONTACT_URL_TAG_NAME).map(|contact_url| &**contact_url) != Some(&*settings.acme_contact_url) {
+		if acme_account.contact_urls() != [&*settings.acme_contact_url] {
+			acme_account.update_contacts(&[&settings.acme_contact_url]).await?;
+		}
+
+		account_key_tags.insert(ACME_CONTACT_URL_TAG_NAME.to_owned(), settings.acme_contact_url.clone().into_owned());
+		azure_key_vault_client.key_set_tags(&account_key, &account_key_tags).await?;
+	}
+
+	acme_account.set_polling_timeouts(
+		Some(std::time::Duration::from_secs(settings.acme_polling_phase_timeout_secs.unwrap_or(120))),
+		Some(std::time::Duration::from_secs(settings.acme_polling_overall_timeout_secs.unwrap_or(240))),
+	);
+
+	let challenge_types = settings.acme_challenge_types.as_deref().unwrap_or(&[acme::ChallengeType::Dns01]);
+	for challenge_type in challenge_types {
+		if !matches!(challenge_type, acme::ChallengeType::Dns01 | acme::ChallengeType::DnsAccount01 | acme::ChallengeType::DnsPersist01) {
+			return Err(anyhow::anyhow!("{} challenges are not supported; only DNS challenges are", challenge_type.name()));
+		}
+	}
+
+	let identifiers: Vec<_> = settings.domain_names.iter().map(|domain_name| acme::Identifier::Dns(domain_name.clone().into_owned())).collect();
+
+	let not_after =
+		settings.acme_requested_certificate_lifetime_secs
+		.map(|lifetime_secs| {
+			let lifetime: time::Duration = std::time::Duration::from_secs(lifetime_secs).try_into().context("requested certificate lifetime is too large")?;
+			time::OffsetDateTime::now_utc().checked_add(lifetime).context("requested certificate lifetime is too large")
+		})
+		.transpose()?;
+
+	let in_flight_order_secret_name = settings.azure_key_vault_acme_order_secret_name.as_deref();
+
+	// Resume the order placed by an earlier invocation if that invocation did not complete it.
+	let in_flight_order =
+		if let Some(secret_name) = in_flight_order_secret_name {
+			let in_flight_order = async {
+				let Some(InFlightOrder { order_url, identifiers: order_identifiers, phase }) = get_in_flight_order(&azure_key_vault_client, secret_name).await? else {
+					return Ok(None);
+				};
+
+				if order_identifiers != identifiers {
+					logger.report_message(format_args!("Not resuming order {order_url} since it was placed for {order_identifiers:?}"));
+					return Ok(None);
+				}
+
+				let order_url = order_url.parse().context("could not parse order URL")?;
+				let order = acme_account.get_order(order_url, challenge_types).await?;
+				Ok::<_, anyhow::Error>(Some((order, phase)))
+			};
+			match in_flight_order.await {
+				Ok(in_flight_order) => in_flight_order,
+				Err(err) => {
+					// Fall back to placing a new order.
+					logger.report_error(&err);
+					None
+				},
+			}
+		}
+		else {
+			None
+		};
+
+	let (mut acme_order, mut csr) =
+		if let Some((acme_order, phase)) = in_flight_order {
+			let csr = match phase {
+				InFlightOrderPhase::Authorizing => None,
+				InFlightOrderPhase::Finalizing { csr } => Some(csr),
+			};
+			(acme_order, csr)
+		}
+		else {
+			let acme_order = match acme_account.place_order(
+				&identifiers,
+				challenge_types,
+				replaces.as_deref(),
+				settings.acme_profile.as_deref(),
+				None,
+				not_after,
+			).await {
+				// An earlier invocation that died before the new certificate was merged into the KeyVault may have already
+				// placed an order that replaces this certificate. Every later order that names it as replaced is rejected,
+				// so renewal would be blocked until the certificate expires.
+				Err(err) if err.downcast_ref::<acme::Problem>().is_some_and(|problem| problem.r#type == acme::ProblemType::AlreadyReplaced) => {
+					logger.report_error(&err);
+					acme_account.place_order(
+						&identifiers,
+						challenge_types,
+						None,
+						settings.acme_profile.as_deref(),
+						None,
+						not_after,
+					).await?
+				},
+
+				acme_order => acme_order?,
+			};
+
+			if let Some(secret_name) = in_flight_order_secret_name {
+				set_in_flight_order(&azure_key_vault_client, secret_name, Some(&InFlightOrder {
+					order_url: acme_order.url().to_string(),
+					identifiers: identifiers.clone(),
+					phase: InFlightOrderPhase::Authorizing,
+				})).await?;
+			}
+
+			(acme_order, None)
+		};
+
+	let order_url = acme_order.url().to_string();
 
 	let certificates = {
 		let azure_management_client = azure::management::Client::new(
@@ -103,87 +300,141 @@ pub async fn main(
 		let certificate = loop {
 			match acme_order {
 				acme::Order::Pending(pending) => {
-					azure_management_client.dns_txt_record_create(
-						&settings.top_level_domain_name,
-						"_acme-challenge",
-						pending.authorizations.iter().map(|authorization| &*authorization.dns_txt_record_content),
-					).await?;
+					// DNS zone name -> TXT record name -> TXT record contents
+					let mut dns_txt_records: std::collections::BTreeMap<&str, std::collections::BTreeMap<String, Vec<String>>> = Default::default();
+					// Same as `dns_txt_records`, but for the records that are left in place after the order completes.
+					let mut dns_persist_txt_records: std::collections::BTreeMap<&str, std::collections::BTreeMap<String, Vec<String>>> = Default::default();
+					for authorization in &pending.authorizations {
+						let acme::Identifier::Dns(domain_name) = &authorization.identifier else {
+							return Err(anyhow::anyhow!("authorization for {} is not for a DNS name", authorization.identifier));
+						};
+
+						let (dns_txt_records, dns_txt_record_label, dns_txt_record_content) = match &authorization.challenge {
+							acme::PendingChallenge::Dns01 { dns_txt_record_content } =>
+								(&mut dns_txt_records, "_acme-challenge", dns_txt_record_content),
+							acme::PendingChallenge::DnsAccount01 { dns_txt_record_label, dns_txt_record_content } =>
+								(&mut dns_txt_records, &**dns_txt_record_label, dns_txt_record_content),
+							acme::PendingChallenge::DnsPersist01 { dns_txt_record_content } =>
+								(&mut dns_persist_txt_records, "_validation-persist", dns_txt_record_content),
+							_ => return Err(anyhow::anyhow!("authorization for {} does not have a DNS challenge", authorization.identifier)),
+						};
+
+						let (dns_zone_name, dns_txt_record_name) = dns_txt_record_name(&settings.azure_dns_zone_names, domain_name, dns_txt_record_label)?;
+						dns_txt_records
+						.entry(dns_zone_name).or_default()
+						.entry(dns_txt_record_name).or_default()
+						.push(dns_txt_record_content.clone());
+					}
 
-					// Don't use `?` to fail immediately. Delete the TXT record first.
+					// Don't use `?` to fail immediately. Delete the TXT records first.
 					let new_acme_order = async {
-						const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
-
-						let name_servers = azure_management_client.dns_zone_name_servers_get(&settings.top_level_domain_name).await?;
-						let name_servers: futures_util::future::JoinAll<_> =
-							name_servers.into_iter()
-							.map(|name_server| tokio::net::lookup_host((name_server, 53)))
-							.collect();
-						let name_servers: Vec<_> =
-							name_servers.await.into_iter()
-							.flatten()
-							.flatten()
-							.flat_map(|socket_addr| [
-								hickory_resolver::config::NameServerConfig::new(socket_addr, hickory_resolver::proto::xfer::Protocol::Udp),
-								hickory_resolver::config::NameServerConfig::new(socket_addr, hickory_resolver::proto::xfer::Protocol::Tcp),
-							])
-							.collect();
-
-						let name: hickory_resolver::Name = "_acme-challenge".parse().expect("hard-coded name is valid");
-						let name = name.append_domain(&settings.top_level_domain_name.parse()?)?;
-
-						let name_str = name.to_utf8();
-
-						let resolver =
-							hickory_resolver::Resolver::builder_with_config(
-								hickory_resolver::config::ResolverConfig::from_parts(None, vec![], name_servers),
-								hickory_resolver::name_server::TokioConnectionProvider::default(),
-							)
-							.build();
-
-						let mut retry_delay = std::time::Duration::from_millis(100);
-
-						loop {
-							let created = logger.report_operation("dns/lookup", &name_str, <log2::ScopedObjectOperation>::Get, async {
-								resolver.clear_cache();
-								match resolver.txt_lookup(name.clone()).await {
-									Ok(_) => Ok(true),
-									Err(err) if err.is_no_records_found() => Ok(false),
-									Err(err) => Err(anyhow::Error::from(err)),
+						// Persistent records usually exist already from an earlier order, in which case there's nothing to write or wait for.
+						for (dns_zone_name, dns_txt_records) in &dns_persist_txt_records {
+							let mut changed_dns_txt_records = vec![];
+							for (dns_txt_record_name, dns_txt_record_contents) in dns_txt_records {
+								if azure_management_client.dns_txt_record_add(dns_zone_name, dns_txt_record_name, dns_txt_record_contents).await? {
+									changed_dns_txt_records.push((dns_txt_record_name, dns_txt_record_contents));
 								}
-							}).await?;
-							if created {
-								break;
 							}
 
-							tokio::time::sleep(retry_delay).await;
-							retry_delay = MAX_RETRY_DELAY.min(retry_delay * 2);
+							if !changed_dns_txt_records.is_empty() {
+								wait_for_dns_txt_records(&azure_management_client, dns_zone_name, changed_dns_txt_records, logger).await?;
+							}
+						}
+
+						for (dns_zone_name, dns_txt_records) in &dns_txt_records {
+							for (dns_txt_record_name, dns_txt_record_contents) in dns_txt_records {
+								azure_management_client.dns_txt_record_create(
+									dns_zone_name,
+									dns_txt_record_name,
+									dns_txt_record_contents.iter().map(|content| &**content),
+								).await?;
+							}
+						}
+
+						for (dns_zone_name, dns_txt_records) in &dns_txt_records {
+							wait_for_dns_txt_records(&azure_management_client, dns_zone_name, dns_txt_records, logger).await?;
 						}
 
-						let new_acme_order = acme_account.complete_authorization(pending).await?;
+						let new_acme_order = acme_account.complete_authorization(&pending).await?;
 						Ok::<_, anyhow::Error>(new_acme_order)
 					};
 					let new_acme_order = new_acme_order.await;
 
-					azure_management_client.dns_txt_record_delete(
-						&settings.top_level_domain_name,
-						"_acme-challenge",
-					).await?;
+					// Don't use `?` to fail immediately. Deactivate the authorizations first.
+					let delete_result = async {
+						for (dns_zone_name, dns_txt_records) in &dns_txt_records {
+							for dns_txt_record_name in dns_txt_records.keys() {
+								azure_management_client.dns_txt_record_delete(
+									dns_zone_name,
+									dns_txt_record_name,
+								).await?;
+							}
+						}
+
+						Ok::<_, anyhow::Error>(())
+					}.await;
+
+					let new_acme_order = match new_acme_order {
+						Ok(new_acme_order) => {
+							delete_result?;
+							new_acme_order
+						},
+
+						Err(err) => {
+							// Don't leave the authorizations pending, since they count towards the ACME server's limit of pending authorizations.
+							//
+							// If the order can be resumed with `azure_key_vault_acme_order_secret_name`, only do this if validation failed.
+							// Other errors like timeouts leave the order resumable, and deactivating its authorizations would make it fail.
+							if in_flight_order_secret_name.is_none() || err.downcast_ref::<acme::StatusError>().is_some() {
+								for authorization in &pending.authorizations {
+									if let Err(err) = acme_account.deactivate_authorization(authorization).await {
+										logger.report_error(&err);
+									}
+								}
+							}
 
-					acme_order = acme::Order::Ready(new_acme_order?);
+							if let Err(delete_err) = delete_result {
+								logger.report_error(&delete_err);
+							}
+
+							return Err(err);
+						},
+					};
+
+					acme_order = acme::Order::Ready(new_acme_order);
 				},
 
 				acme::Order::Ready(ready) => {
+					// An earlier invocation that created the CSR but died before the order became valid left a pending certificate operation
+					// in the KeyVault, so reuse that CSR instead of creating a new one.
 					let csr =
-						azure_key_vault_client.csr_create(
-							&settings.azure_key_vault_certificate_name,
-							&settings.top_level_domain_name,
-							settings.azure_key_vault_certificate_key_type,
-						).await?;
+						if let Some(csr) = csr.take() {
+							csr
+						}
+						else {
+							let csr =
+								azure_key_vault_client.csr_create(
+									&settings.azure_key_vault_certificate_name,
+									&identifiers,
+									settings.azure_key_vault_certificate_key_type,
+								).await?;
+
+							if let Some(secret_name) = in_flight_order_secret_name {
+								set_in_flight_order(&azure_key_vault_client, secret_name, Some(&InFlightOrder {
+									order_url: order_url.clone(),
+									identifiers: identifiers.clone(),
+									phase: InFlightOrderPhase::Finalizing { csr: csr.clone() },
+								})).await?;
+							}
+
+							csr
+						};
 					acme_order = acme::Order::Valid(acme_account.finalize_order(ready, csr).await?);
 				},
 
 				acme::Order::Valid(valid) =>
-					break acme_account.download_certificate(valid).await?,
+					break acme_account.download_certificate(valid, settings.acme_preferred_chain.as_deref()).await?,
 			}
 		};
 
@@ -220,6 +471,10 @@ pub async fn main(
 		&certificates,
 	).await?;
 
+	if let Some(secret_name) = in_flight_order_secret_name {
+		set_in_flight_order(&azure_key_vault_client, secret_name, None).await?;
+	}
+
 	logger.report_state(
 		"azure/key_vault/certificate",
 		(&settings.azure_key_vault_name, &settings.azure_key_vault_certificate_name),
@@ -233,6 +488,158 @@ pub async fn main(
 	Ok(())
 }
 
+pub async fn revoke_cert(
+	azure_auth: &azure::Auth,
+	settings: &Settings<'_>,
+	logger: &log2::Logger,
+) -> anyhow::Result<()> {
+	let user_agent = user_agent();
+
+	let azure_key_vault_client = azure::key_vault::Client::new(
+		&settings.azure_key_vault_name,
+		azure_auth,
+		user_agent.clone(),
+		logger,
+	).context("could not initialize Azure KeyVault API client")?;
+
+	let acme_client = acme::Client::new(
+		settings.acme_directory_url.0.clone(),
+		user_agent,
+		logger,
+	).await.context("could not initialize ACME API client")?;
+
+	let certificate =
+		azure_key_vault_client.certificate_get(&settings.azure_key_vault_certificate_name).await?
+		.context("certificate does not exist")?;
+
+	let account_key =
+		azure_key_vault_client.key_get(&settings.azure_key_vault_acme_account_key_name).await?
+		.context("ACME account key does not exist")?;
+
+	let mut acme_account = acme_client.find_account(&account_key).await.context("could not initialize ACME API client")?;
+
+	acme_account.revoke_certificate(&certificate.der, settings.acme_revocation_reason).await?;
+
+	logger.report_state(
+		"azure/key_vault/certificate",
+		(&settings.azure_key_vault_name, &settings.azure_key_vault_certificate_name),
+		format_args!("version {} revoked", certificate.version),
+	);
+
+	Ok(())
+}
+
+pub async fn rollover_account_key(
+	azure_auth: &azure::Auth,
+	settings: &Settings<'_>,
+	logger: &log2::Logger,
+) -> anyhow::Result<()> {
+	let user_agent = user_agent();
+
+	let azure_key_vault_client = azure::key_vault::Client::new(
+		&settings.azure_key_vault_name,
+		azure_auth,
+		user_agent.clone(),
+		logger,
+	).context("could not initialize Azure KeyVault API client")?;
+
+	let acme_client = acme::Client::new(
+		settings.acme_directory_url.0.clone(),
+		user_agent.clone(),
+		logger,
+	).await.context("could not initialize ACME API client")?;
+
+	let old_account_key =
+		azure_key_vault_client.key_get(&settings.azure_key_vault_acme_account_key_name).await?
+		.context("ACME account key does not exist")?;
+
+	let acme_account = acme_client.find_account(&old_account_key).await.context("could not initialize ACME API client")?;
+
+	// This creates a new version of the same KeyVault key, so all subsequent invocations automatically use the new key.
+	//
+	// If the key change below fails, the new version is disabled so that the old version becomes the current one again.
+	// Otherwise the next invocation of `renew-cert` would register a new ACME account with the new key.
+	// But if the ACME server applied the key change and only its response was lost, the new version must stay current.
+	let (kty, crv) = settings.azure_key_vault_acme_account_key_type;
+	let new_account_key =
+		azure_key_vault_client.key_create(
+			&settings.azure_key_vault_acme_account_key_name,
+			kty,
+			crv,
+		).await?;
+
+	if let Err(err) = acme_account.change_key(&new_account_key).await {
+		let key_changed =
+			if err.downcast_ref::<acme::Problem>().is_some() {
+				// The ACME server rejected the key change.
+				false
+			}
+			else {
+				let acme_client = acme::Client::new(
+					settings.acme_directory_url.0.clone(),
+					user_agent,
+					logger,
+				).await.context("could not initialize ACME API client")?;
+
+				match acme_client.find_account(&new_account_key).await {
+					Ok(_) => true,
+
+					Err(find_err) if find_err.downcast_ref::<acme::Problem>().is_some_and(|problem| problem.r#type == acme::ProblemType::AccountDoesNotExist) => false,
+
+					Err(find_err) => return Err(find_err.context(format!(
+						"could not change ACME account key ({err:#}), and could not determine whether the key change was applied; \
+						the new version of the KeyVault key is still current",
+					))),
+				}
+			};
+
+		if !key_changed {
+			azure_key_vault_client.key_disable(&new_account_key).await.context("could not disable new version of ACME account key after failed key change")?;
+
+			let current_account_key =
+				azure_key_vault_client.key_get(&settings.azure_key_vault_acme_account_key_name).await?
+				.context("ACME account key does not exist")?;
+			if current_account_key.kid() != old_account_key.kid() {
+				return Err(err.context(format!(
+					"could not change ACME account key, and the current version of the KeyVault key {:?} is not the old version {:?}",
+					current_account_key.kid(),
+					old_account_key.kid(),
+				)));
+			}
+
+			return Err(err.context("could not change ACME account key; the old version of the KeyVault key is still current"));
+		}
+
+		logger.report_error(&err.context("ACME server applied the key change even though the request failed"));
+	}
+
+	// Carry over the contact URL and the terms of service URL that the account agreed to.
+	if !old_account_key.tags().is_empty() {
+		azure_key_vault_client.key_set_tags(&new_account_key, old_account_key.tags()).await?;
+	}
+
+	azure_key_vault_client.key_disable(&old_account_key).await?;
+
+	logger.report_state(
+		"azure/key_vault/key",
+		(&settings.azure_key_vault_name, &settings.azure_key_vault_acme_account_key_name),
+		"rolled over",
+	);
+
+	Ok(())
+}
+
+/// The name of the tag of the ACME account key that records the contact URL that was last sent to the ACME server.
+const ACME_CONTACT_URL_TAG_NAME: &str = "acme-contact-url";
+
+/// The name of the tag of the ACME account key that records the URL of the terms of service that the ACME account agreed to.
+const ACME_TERMS_OF_SERVICE_URL_TAG_NAME: &str = "acme-terms-of-service-url";
+
+fn user_agent() -> http_common::HeaderValue {
+	concat!("github.com/Arnavion/acme-azure-function ", env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"))
+	.parse().expect("hard-coded user agent is valid HeaderValue")
+}
+
 #[derive(serde::Deserialize)]
 pub struct Settings<'a> {
 	/// The directory URL of the ACME server
@@ -242,6 +649,61 @@ pub struct Settings<'a> {
 	#[serde(borrow)]
 	acme_contact_url: std::borrow::Cow<'a, str>,
 
+	/// The key identifier for External Account Binding, if the ACME server requires it.
+	#[serde(borrow)]
+	acme_eab_key_id: Option<std::borrow::Cow<'a, str>>,
+
+	/// The name of the certificate profile to request, like `"shortlived"` or `"tlsserver"` for Let's Encrypt.
+	///
+	/// It must be one of the profiles offered by the ACME server. If not set, the ACME server's default profile is used.
+	#[serde(borrow)]
+	acme_profile: Option<std::borrow::Cow<'a, str>>,
+
+	/// The requested lifetime of the new certificate in seconds, sent to the ACME server as the order's `notAfter`.
+	///
+	/// Not all ACME servers honor this. If not set, the ACME server decides the lifetime.
+	acme_requested_certificate_lifetime_secs: Option<u64>,
+
+	/// The common name of the issuer of the topmost certificate of the preferred certificate chain, if the ACME server offers alternate chains.
+	///
+	/// If not set, or if none of the chains match, the default chain is used.
+	#[serde(borrow)]
+	acme_preferred_chain: Option<std::borrow::Cow<'a, str>>,
+
+	/// How long to wait for each of the order's phases (order creation, authorization, finalization) to complete, in seconds.
+	///
+	/// Defaults to 120.
+	acme_polling_phase_timeout_secs: Option<u64>,
+
+	/// How long to wait for the order to become valid, in seconds.
+	///
+	/// Defaults to 240, which is less than the default timeout of Functions on the Consumption plan.
+	acme_polling_overall_timeout_secs: Option<u64>,
+
+	/// The reason sent to the ACME server when the certificate is revoked by the `revoke-cert` function.
+	///
+	/// One of the reason names in RFC 5280, like `keyCompromise` or `superseded`. If not set, no reason is sent.
+	acme_revocation_reason: Option<acme::RevocationReason>,
+
+	/// The types of challenges used to validate the domain names, in order of preference. Any of `dns-01`, `dns-account-01` and `dns-persist-01`.
+	///
+	/// `dns-account-01` uses a TXT record specific to the ACME account, so it does not conflict with other ACME clients
+	/// that validate the same domain names. `dns-persist-01` uses a TXT record that is left in place so that later renewals
+	/// do not need to write any DNS records. Defaults to `["dns-01"]`.
+	acme_challenge_types: Option<Vec<acme::ChallengeType>>,
+
+	/// The URL of the ACME server's new terms of service, set to acknowledge them after they have been reviewed.
+	///
+	/// The URL of the terms of service that the ACME account agreed to is recorded in a tag of the ACME account key.
+	/// A warning is logged if the ACME server's directory advertises a different URL that has not been acknowledged.
+	#[serde(borrow)]
+	acme_terms_of_service_url: Option<std::borrow::Cow<'a, str>>,
+
+	/// If `true`, refuse to renew the certificate while the ACME server's terms of service have changed and not been acknowledged.
+	///
+	/// Defaults to `false`.
+	acme_terms_of_service_acknowledgement_required: Option<bool>,
+
 	/// The name of the Azure resource group
 	#[serde(borrow)]
 	azure_resource_group_name: std::borrow::Cow<'a, str>,
@@ -256,6 +718,19 @@ pub struct Settings<'a> {
 	#[serde(borrow)]
 	azure_key_vault_acme_account_key_name: std::borrow::Cow<'a, str>,
 
+	/// The name of the KeyVault secret that contains the base64url-encoded HMAC key for External Account Binding.
+	///
+	/// Must be set if and only if `acme_eab_key_id` is set.
+	#[serde(borrow)]
+	azure_key_vault_acme_eab_hmac_key_secret_name: Option<std::borrow::Cow<'a, str>>,
+
+	/// The name of the KeyVault secret used to save the order in progress, so that an invocation that dies before completing the order
+	/// can be resumed by the next invocation instead of placing a new order.
+	///
+	/// If not set, every invocation places a new order.
+	#[serde(borrow)]
+	azure_key_vault_acme_order_secret_name: Option<std::borrow::Cow<'a, str>>,
+
 	/// The parameters used for the private key of the ACME account key if it needs to be created.
 	#[serde(deserialize_with = "deserialize_key_vault_acme_account_key_type")]
 	azure_key_vault_acme_account_key_type: (azure::key_vault::EcKty, acme::EcCurve),
@@ -270,9 +745,148 @@ pub struct Settings<'a> {
 	#[serde(deserialize_with = "deserialize_key_vault_certificate_key_type")]
 	azure_key_vault_certificate_key_type: azure::key_vault::CreateCsrKeyType,
 
-	/// The domain name to request the TLS certificate for
+	/// The names of the Azure DNS zones that contain the `_acme-challenge` TXT records of `domain_names`.
+	///
+	/// Each domain name is validated using the zone with the longest name that it is a subdomain of.
+	#[serde(borrow)]
+	azure_dns_zone_names: Vec<std::borrow::Cow<'a, str>>,
+
+	/// The domain names to request the TLS certificate for. Wildcard names like `*.example.com` are allowed.
+	///
+	/// The first name is used as the subject CN of the certificate.
 	#[serde(borrow)]
-	top_level_domain_name: std::borrow::Cow<'a, str>,
+	domain_names: Vec<std::borrow::Cow<'a, str>>,
+}
+
+/// An order that has not been driven to completion yet, saved in a KeyVault secret so that it can be resumed by the next invocation.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct InFlightOrder {
+	order_url: String,
+	identifiers: Vec<acme::Identifier>,
+	phase: InFlightOrderPhase,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+enum InFlightOrderPhase {
+	#[serde(rename = "authorizing")]
+	Authorizing,
+
+	/// The CSR of the pending certificate operation in the KeyVault has been created and sent to the ACME server.
+	#[serde(rename = "finalizing")]
+	Finalizing {
+		csr: String,
+	},
+}
+
+async fn get_in_flight_order(azure_key_vault_client: &azure::key_vault::Client<'_>, secret_name: &str) -> anyhow::Result<Option<InFlightOrder>> {
+	let Some(log2::Secret(in_flight_order)) = azure_key_vault_client.secret_get(secret_name).await? else {
+		return Ok(None);
+	};
+
+	let in_flight_order = serde_json::from_str(&in_flight_order).context("could not parse in-flight order")?;
+	Ok(in_flight_order)
+}
+
+/// Saves `in_flight_order`, or clears the saved order if `None`.
+///
+/// The secret is overwritten with `null` rather than deleted, since a deleted secret must be purged before it can be created again.
+async fn set_in_flight_order(
+	azure_key_vault_client: &azure::key_vault::Client<'_>,
+	secret_name: &str,
+	in_flight_order: Option<&InFlightOrder>,
+) -> anyhow::Result<()> {
+	let in_flight_order = serde_json::to_string(&in_flight_order).context("could not serialize in-flight order")?;
+	azure_key_vault_client.secret_set(secret_name, &in_flight_order).await?;
+	Ok(())
+}
+
+/// Returns the DNS zone that contains the `dns_txt_record_label` TXT record of `domain_name`, and the name of that record relative to the zone.
+///
+/// `dns_txt_record_label` is the label of the TXT record under `domain_name`, like `_acme-challenge`.
+fn dns_txt_record_name<'a>(
+	dns_zone_names: &'a [std::borrow::Cow<'_, str>],
+	domain_name: &str,
+	dns_txt_record_label: &str,
+) -> anyhow::Result<(&'a str, String)> {
+	dns_zone_names.iter()
+	.filter_map(|dns_zone_name| {
+		if domain_name == dns_zone_name {
+			Some((&**dns_zone_name, dns_txt_record_label.to_owned()))
+		}
+		else {
+			let subdomain = domain_name.strip_suffix(&**dns_zone_name)?.strip_suffix('.')?;
+			Some((&**dns_zone_name, format!("{dns_txt_record_label}.{subdomain}")))
+		}
+	})
+	.max_by_key(|(dns_zone_name, _)| dns_zone_name.len())
+	.with_context(|| format!("{domain_name} is not in any of the configured Azure DNS zones"))
+}
+
+/// Waits until the authoritative name servers of the zone serve all of the given contents for each of the given TXT records.
+///
+/// The record set may contain other values too, such as a long-lived record set that the contents were added to.
+async fn wait_for_dns_txt_records<'a>(
+	azure_management_client: &azure::management::Client<'_>,
+	dns_zone_name: &str,
+	dns_txt_records: impl IntoIterator<Item = (&'a String, &'a Vec<String>)>,
+	logger: &log2::Logger,
+) -> anyhow::Result<()> {
+	const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+	let name_servers = azure_management_client.dns_zone_name_servers_get(dns_zone_name).await?;
+	let name_servers: futures_util::future::JoinAll<_> =
+		name_servers.into_iter()
+		.map(|name_server| tokio::net::lookup_host((name_server, 53)))
+		.collect();
+	let name_servers: Vec<_> =
+		name_servers.await.into_iter()
+		.flatten()
+		.flatten()
+		.flat_map(|socket_addr| [
+			hickory_resolver::config::NameServerConfig::new(socket_addr, hickory_resolver::proto::xfer::Protocol::Udp),
+			hickory_resolver::config::NameServerConfig::new(socket_addr, hickory_resolver::proto::xfer::Protocol::Tcp),
+		])
+		.collect();
+
+	let resolver =
+		hickory_resolver::Resolver::builder_with_config(
+			hickory_resolver::config::ResolverConfig::from_parts(None, vec![], name_servers),
+			hickory_resolver::name_server::TokioConnectionProvider::default(),
+		)
+		.build();
+
+	let dns_zone_name: hickory_resolver::Name = dns_zone_name.parse()?;
+
+	for (dns_txt_record_name, dns_txt_record_contents) in dns_txt_records {
+		let name: hickory_resolver::Name = dns_txt_record_name.parse()?;
+		let name = name.append_domain(&dns_zone_name)?;
+
+		let name_str = name.to_utf8();
+
+		let mut retry_delay = std::time::Duration::from_millis(100);
+
+		loop {
+			let propagated = logger.report_operation("dns/lookup", &name_str, <log2::ScopedObjectOperation>::Get, async {
+				resolver.clear_cache();
+				match resolver.txt_lookup(name.clone()).await {
+					Ok(txt_records) => {
+						let txt_records: Vec<Vec<u8>> = txt_records.iter().map(|txt_record| txt_record.txt_data().concat()).collect();
+						Ok(dns_txt_record_contents.iter().all(|content| txt_records.iter().any(|txt_record| txt_record == content.as_bytes())))
+					},
+					Err(err) if err.is_no_records_found() => Ok(false),
+					Err(err) => Err(anyhow::Error::from(err)),
+				}
+			}).await?;
+			if propagated {
+				break;
+			}
+
+			tokio::time::sleep(retry_delay).await;
+			retry_delay = MAX_RETRY_DELAY.min(retry_delay * 2);
+		}
+	}
+
+	Ok(())
 }
 
 fn deserialize_key_vault_acme_account_key_type<'de, D>(deserializer: D) -> Result<(azure::key_vault::EcKty, acme::EcCurve), D::Error>
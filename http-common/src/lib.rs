@@ -19,6 +19,8 @@ pub use hyper::{
 		HeaderMap,
 		HeaderName,
 		HeaderValue,
+		LINK,
+		RETRY_AFTER,
 	},
 };
 
@@ -206,12 +208,23 @@ pub struct ResponseBody<R> {
 }
 
 impl<R> ResponseBody<R> where R: std::io::Read {
+	pub fn content_type(&self) -> &HeaderValue {
+		&self.content_type
+	}
+
 	pub fn as_json<'de, T>(&'de mut self) -> anyhow::Result<T> where T: serde::Deserialize<'de> {
+		self.as_json_with_content_type("application/json")
+	}
+
+	/// Like [`ResponseBody::as_json`], but for JSON-based media types other than `application/json`, like `application/problem+json`.
+	pub fn as_json_with_content_type<'de, T>(&'de mut self, expected_content_type: &str) -> anyhow::Result<T> where T: serde::Deserialize<'de> {
 		let is_json =
 			self.content_type.to_str()
-			.is_ok_and(|content_type| content_type == "application/json" || content_type.starts_with("application/json;"));
+			.is_ok_and(|content_type|
+				content_type.strip_prefix(expected_content_type)
+				.is_some_and(|rest| rest.is_empty() || rest.starts_with(';')));
 		if !is_json {
-			return Err(anyhow::anyhow!("response body does not have content-type:application/json"));
+			return Err(anyhow::anyhow!("response body does not have content-type:{expected_content_type}"));
 		}
 
 		let first = &self.first[..];
@@ -271,7 +284,7 @@ pub fn get_retry_after(
 	min: std::time::Duration,
 	max: std::time::Duration,
 ) -> anyhow::Result<std::time::Duration> {
-	let Some(retry_after) = headers.get(hyper::header::RETRY_AFTER) else { return Ok(min); };
+	let Some(retry_after) = headers.get(RETRY_AFTER) else { return Ok(min); };
 
 	let retry_after = retry_after.to_str().context("could not parse retry-after header")?;
 
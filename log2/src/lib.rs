@@ -24,6 +24,10 @@ impl Logger {
 		self.report_inner::<&str, _>(Report::Message { message });
 	}
 
+	pub fn report_warning<D>(&self, message: D) where D: Copy + std::fmt::Display + serde::Serialize {
+		self.report_inner::<&str, _>(Report::Warning { message });
+	}
+
 	pub async fn report_operation<IID, D, F, ID>(&self, object_type: &str, object_id: IID, operation: ScopedObjectOperation<D>, f: F) -> F::Output
 	where
 		IID: Into<ObjectId<ID>>,
@@ -102,7 +106,11 @@ impl Logger {
 		}
 
 		log::log!(
-			if matches!(report, Report::Error { .. }) { log::Level::Error } else { log::Level::Info },
+			match report {
+				Report::Error { .. } => log::Level::Error,
+				Report::Warning { .. } => log::Level::Warn,
+				_ => log::Level::Info,
+			},
 			"[{}] {report:?}",
 			timestamp.format(time2::RFC3339_MILLISECONDS).expect("could not format time"),
 		);
@@ -175,6 +183,11 @@ where
 				serializer.serialize_field("Message", message)?;
 			},
 
+			Report::Warning { message } => {
+				serializer.serialize_field("Level", &SerializeWith(log::Level::Warn))?;
+				serializer.serialize_field("Message", message)?;
+			},
+
 			Report::ObjectOperation { object_type, object_id, operation } => {
 				serializer.serialize_field("Level", &SerializeWith(log::Level::Info))?;
 				serializer.serialize_field("ObjectType", object_type)?;
@@ -225,6 +238,10 @@ enum Report<'a, ID, D> {
 		message: D,
 	},
 
+	Warning {
+		message: D,
+	},
+
 	ObjectOperation {
 		object_type: &'a str,
 		object_id: ObjectId<ID>,
@@ -268,6 +285,11 @@ where
 				.field("message", &format_args!("{message}"))
 				.finish(),
 
+			Report::Warning { message } =>
+				f.debug_struct("Warning")
+				.field("message", &format_args!("{message}"))
+				.finish(),
+
 			Report::ObjectOperation { object_type, object_id, operation } =>
 				f.debug_struct("ObjectOperation")
 				.field("object_type", &format_args!("{object_type}"))
@@ -26,6 +26,24 @@ impl function_worker::Handler for Handler {
 				return Ok(true);
 			}
 
+			if path == "rollover-account-key" {
+				function_renew_cert::rollover_account_key(
+					azure_auth,
+					settings,
+					logger,
+				).await?;
+				return Ok(true);
+			}
+
+			if path == "revoke-cert" {
+				function_renew_cert::revoke_cert(
+					azure_auth,
+					settings,
+					logger,
+				).await?;
+				return Ok(true);
+			}
+
 			Ok(false)
 		})
 	}